@@ -6,8 +6,7 @@ local _root
 
 --- utils.
 local au = vim.api.nvim_create_autocmd
-local packadd = function(path)
-	local p = dofile(path)
+local packadd = function(p)
 	if p ~= nil then
 		log.debug("[packadd]", p)
 		vim.cmd("packadd " .. p)
@@ -18,6 +17,7 @@ end
 local M = {}
 
 M.denops_plugins = {}
+M.followers = {}
 
 M.loaded_plugins = {}
 
@@ -29,6 +29,11 @@ M.new = function(opts)
 	_root = opts.root
 	self.root = opts.root
 	self.timer = opts.timer
+	-- exported with `--format lua-module`.
+	local module_path = opts.root .. "/bundler.lua"
+	if vim.fn.filereadable(module_path) == 1 then
+		self.module = dofile(module_path)
+	end
 	log.new({
 		plugin = "bundler-nvim",
 		level = opts.log_level,
@@ -37,73 +42,218 @@ M.new = function(opts)
 	return self
 end
 
+--- value exported under `key`.
+M.read = function(self, key)
+	if self.module then
+		return self.module.values[key]
+	end
+	return dofile(self.root .. "/" .. key)
+end
+
+--- run code exported under `key`.
+M.run = function(self, key)
+	if self.module then
+		local code = self.module.codes[key] or ""
+		return assert(load(code, "=" .. key))()
+	end
+	return dofile(self.root .. "/" .. key)
+end
+
 M.setup_loader = function(self)
 	log.debug("[setup_loader] start", self.root)
-	for _, id in ipairs(dofile(self.root .. "/startup_keys")) do
+	-- options and globals of the bundler config
+	local ok, err_msg = pcall(self.run, self, "startup_config")
+	if not ok then
+		log.error("startup error:", err_msg or "-- no msg --")
+	end
+	for _, id in ipairs(self:read("startup_keys")) do
 		self:startup(id)
 	end
-	for _, ev in ipairs(dofile(self.root .. "/event_keys")) do
+	for _, ev in ipairs(self:read("event_keys")) do
 		log.debug("ev:", ev)
 		au({ ev }, {
 			pattern = "*",
 			once = true,
 			callback = function()
-				self:load_plugins(self.root .. "/events/" .. ev)
+				self:load_plugins("events/" .. ev)
 			end,
 		})
 	end
-	for _, ft in ipairs(dofile(self.root .. "/filetype_keys")) do
+	for _, ft in ipairs(self:read("filetype_keys")) do
 		log.debug("ft:", ft)
 		au({ "FileType" }, {
 			pattern = ft,
 			once = true,
 			callback = function()
-				self:load_plugins(self.root .. "/filetypes/" .. ft)
+				self:load_plugins("filetypes/" .. ft)
 			end,
 		})
 	end
-	for _, cmd in ipairs(dofile(self.root .. "/command_keys")) do
+	for _, cmd in ipairs(self:read("command_keys")) do
 		log.debug("cmd:", cmd)
 		au({ "CmdUndefined" }, {
 			pattern = cmd,
 			once = true,
 			callback = function()
-				self:load_plugins(self.root .. "/commands/" .. cmd)
+				self:load_plugins("commands/" .. cmd)
+			end,
+		})
+	end
+	for name, stub in pairs(self:read("command_stubs") or {}) do
+		log.debug("stub:", name)
+		self:stub_command(name, stub)
+	end
+	for _, fn in ipairs(self:read("function_keys")) do
+		log.debug("fn:", fn)
+		au({ "FuncUndefined" }, {
+			pattern = fn,
+			once = true,
+			callback = function()
+				self:load_plugins("functions/" .. fn)
+			end,
+		})
+	end
+	for _, cs in ipairs(self:read("colorscheme_keys")) do
+		log.debug("cs:", cs)
+		au({ "ColorSchemePre" }, {
+			pattern = cs,
+			once = true,
+			callback = function()
+				self:load_plugins("colorschemes/" .. cs)
 			end,
 		})
 	end
-	table.insert(package.loaders, 1, function(mod_name)
+	-- markers not found yet, and directories already searched for them.
+	local markers = {}
+	for _, marker in ipairs(self:read("root_marker_keys")) do
+		markers[marker] = true
+	end
+	local checked_dirs = {}
+	if next(markers) ~= nil then
+		log.debug("markers:", vim.tbl_keys(markers))
+		au({ "BufReadPost", "BufNewFile" }, {
+			pattern = "*",
+			callback = function(args)
+				local path = vim.api.nvim_buf_get_name(args.buf)
+				if path == "" then
+					return
+				end
+				local dir = vim.fs.dirname(vim.fn.fnamemodify(path, ":p"))
+				-- parents of a searched directory have been searched too.
+				while dir ~= nil and not checked_dirs[dir] do
+					checked_dirs[dir] = true
+					for marker in pairs(markers) do
+						if vim.loop.fs_stat(dir .. "/" .. marker) ~= nil then
+							markers[marker] = nil
+							self:load_plugins("root_markers/" .. marker)
+						end
+					end
+					local parent = vim.fs.dirname(dir)
+					dir = parent ~= dir and parent or nil
+				end
+				-- returning true deletes the autocmd.
+				return next(markers) == nil
+			end,
+		})
+	end
+	au({ "CmdlineLeave" }, {
+		pattern = ":",
+		callback = function()
+			local cmd, tag = vim.fn.getcmdline():match("^%s*(%a+)!?%s+(.-)%s*$")
+			if cmd == nil or ("help"):sub(1, #cmd) ~= cmd then
+				return
+			end
+			self.help_tags = self.help_tags or self:read("help_tags")
+			local id = self.help_tags[tag]
+			if id ~= nil then
+				self:load_plugin(id)
+			end
+		end,
+	})
+	-- module paths and owners are resolved at build time.
+	local lua_modules = self:read("lua_modules")
+	local lua_module_owners = self:read("lua_module_owners")
+	-- the user's `lua/` comes first on runtimepath, so its modules are left to `require`.
+	local user_lua = vim.fn.stdpath("config") .. "/lua/"
+	local is_user_module = function(mod_name)
+		local path = user_lua .. mod_name:gsub("%.", "/")
+		return vim.loop.fs_stat(path .. ".lua") ~= nil or vim.loop.fs_stat(path .. "/init.lua") ~= nil
+	end
+	-- after `package.preload`, as `require` checks it first.
+	table.insert(package.loaders, 2, function(mod_name)
 		if not self.loaded_modules[mod_name] then
 			self.loaded_modules[mod_name] = true
-
-			local ok, ids = pcall(dofile, self.root .. "/modules/" .. mod_name)
-			if ok then
-				for _, id in ipairs(ids) do
-					self:load_plugin(id)
+			for _, id in ipairs(lua_module_owners[mod_name] or {}) do
+				self:load_plugin(id)
+			end
+			-- loading the plugin may have required the module already.
+			if package.loaded[mod_name] ~= nil then
+				return function()
+					return package.loaded[mod_name]
 				end
 			end
 		end
+		local path = lua_modules[mod_name]
+		if path ~= nil and not is_user_module(mod_name) then
+			local chunk, err = loadfile(path)
+			return chunk or err
+		end
 	end)
 	vim.defer_fn(function()
-		self:load_plugins(self.root .. "/timer_clients")
+		self:load_plugins("timer_clients")
 	end, self.timer)
-	self.denops_plugins = dofile(self.root .. "/denops_clients")
+	self.denops_plugins = self:read("denops_clients")
+	self.followers = self:read("followers")
+	-- eager plugins are loaded by vim itself, after this config.
+	au({ "VimEnter" }, {
+		once = true,
+		callback = function()
+			self:load_plugins("startup_followers")
+		end,
+	})
 	log.debug("[setup_loader] end")
 end
 
 M.startup = function(self, id)
 	log.debug("[startup] start", id)
-	local ok, err_msg = pcall(dofile, self.root .. "/startup/" .. id)
+	local ok, err_msg = pcall(self.run, self, "startup/" .. id)
 	if not ok then
 		log.error(id, "startup error:", err_msg or "-- no msg --")
 	end
 	log.debug("[startup] end", id)
 end
 
+--- define `name`, which loads its plugins and runs the invocation again.
+M.stub_command = function(self, name, stub)
+	vim.api.nvim_create_user_command(name, function(opts)
+		vim.api.nvim_del_user_command(name)
+		self:load_plugins("commands/" .. name)
+		local cmd = { cmd = name, bang = opts.bang, mods = opts.smods }
+		if opts.args ~= "" then
+			cmd.args = { opts.args }
+		end
+		if type(stub.range) == "number" then
+			if opts.range > 0 then
+				cmd.count = opts.count
+			end
+		elseif opts.range == 1 then
+			cmd.range = { opts.line1 }
+		elseif opts.range == 2 then
+			cmd.range = { opts.line1, opts.line2 }
+		end
+		vim.cmd(cmd)
+	end, {
+		nargs = stub.nargs,
+		range = stub.range,
+		bang = stub.bang,
+		complete = stub.complete,
+	})
+end
+
 M.config = function(self, id, is_pre)
 	log.debug(is_pre and "[pre_config]" or "[post_config]", "start", id)
-	local dir = is_pre and "/pre_config/" or "/post_config/"
-	local ok, err_msg = pcall(dofile, self.root .. dir .. id)
+	local dir = is_pre and "pre_config/" or "post_config/"
+	local ok, err_msg = pcall(self.run, self, dir .. id)
 	if not ok then
 		log.error(id, "configure error:", err_msg or "-- no msg --")
 	end
@@ -111,11 +261,9 @@ M.config = function(self, id, is_pre)
 end
 
 M.load_denops = function(self, id)
-	local path = dofile(self.root .. "/rtp/" .. id)
-	local candidates = vim.fn.globpath(path, "denops/*/main.ts", true, true)
-	for _, c in ipairs(candidates) do
-		local name = vim.fn.fnamemodify(c, ":h:t")
-		local script = path .. "/denops/" .. name .. "/main.ts"
+	-- resolved at build time.
+	for _, c in ipairs(self:read("denops/" .. id)) do
+		local name, script = c.name, c.script
 		local ok, status = pcall(vim.fn["denops#server#status"])
 		if not ok then
 			log.error(id, "load error: `denops.vim` has not been loaded yet.")
@@ -136,20 +284,25 @@ M.load_plugin = function(self, id)
 		log.debug("[load_plugin] start", id)
 		self.loaded_plugins[id] = true
 		self:config(id, true)
-		self:load_plugins(self.root .. "/depend_plugins/" .. id)
-		self:load_plugins(self.root .. "/depend_groups/" .. id)
-		self:load_plugins(self.root .. "/plugins/" .. id)
-		packadd(self.root .. "/plugin/" .. id)
+		self:load_plugins("depend_plugins/" .. id)
+		self:load_plugins("depend_groups/" .. id)
+		self:load_plugins("plugins/" .. id)
+		packadd(self:read("plugin/" .. id))
+		-- `:packadd` defines the ftdetect autocmds sourced at startup again.
+		pcall(vim.api.nvim_del_augroup_by_name, "bundler_ftdetect_" .. id)
 		if self.denops_plugins[id] then
 			self:load_denops(id)
 		end
 		self:config(id, false)
+		for _, p in ipairs(self.followers[id] or {}) do
+			self:load_plugin(p)
+		end
 		log.debug("[load_plugin] end", id)
 	end
 end
 
-M.load_plugins = function(self, path)
-	for _, p in ipairs(dofile(path)) do
+M.load_plugins = function(self, key)
+	for _, p in ipairs(self:read(key) or {}) do
 		self:load_plugin(p)
 	end
 end
@@ -9,13 +9,24 @@
 ---@field new fun(opts: Options): Bundler
 ---@field setup_loader fun(self: Bundler)
 ---@field startup fun(self: Bundler, id: string)
+---@field stub_command fun(self: Bundler, name: string, stub: CommandStub)
 ---@field config fun(self: Bundler, id: string, is_pre: boolean)
 ---@field loaded_plugins { [string]: boolean }
 ---@field loaded_modules { [string]: boolean }
+---@field followers { [string]: string[] }
 ---@field load_plugin fun(self: Bundler, id: string)
----@field load_plugins fun(self: Bundler, path: string)
+---@field load_plugins fun(self: Bundler, key: string)
+---@field module? { values: { [string]: any }, codes: { [string]: string } }
+---@field read fun(self: Bundler, key: string): any
+---@field run fun(self: Bundler, key: string)
 ---@field get_root fun(): string
 
+---@class CommandStub
+---@field nargs string | number
+---@field range boolean | string | number
+---@field bang boolean
+---@field complete? string
+
 ---@class LoggerLevelConfig
 ---@field name string
 ---@field hl string
@@ -1,30 +1,57 @@
+//! plugins indexed by their load triggers, and their export for the loader.
+
+mod backend;
 mod config;
 mod export;
+mod flatten;
 mod merge;
+mod output;
+mod scan;
+pub use crate::bundle::backend::{
+    write_bundle, Backend, JsonBackend, LuaBackend, LuaModuleBackend, Registry, Sink, Value,
+    VimBackend,
+};
 pub use crate::bundle::config::{
-    AfterOption, Bundle, Component, Info, LoadOption, PluginId, PluginPath,
+    AfterCode, AfterOption, Bundle, Component, DenopsScript, EagerRuntime, HelpTag, Info,
+    LoadOption, LuaModule, PluginId, PluginPath,
 };
-pub use crate::bundle::export::{ExportOption, Exporter};
+pub use crate::bundle::export::ExportOption;
 use crate::bundle::merge::merge_vector;
+pub use crate::bundle::output::Output;
+pub(crate) use crate::bundle::output::{DirOutput, TarOutput};
+use crate::bundle::scan::{
+    find_denops_scripts, find_ftdetect_scripts, find_help_tags, find_lua_modules,
+    find_runtime_collisions,
+};
+use crate::constant::dir::INFO;
+use crate::constant::file::{BUNDLER_BIN, STAMP};
 use crate::content;
-use anyhow::Result;
-use std::collections::HashMap;
+pub use crate::util::file::SyncStats;
+use crate::util::file::{read_dir_entries, read_entries, reuse_unchanged, swap_link};
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-fn mk_component<'a>(
-    id_table: &'a content::IdTable,
-    package: &'a content::Package,
-) -> Component<'a> {
+fn mk_component(
+    id_table: &content::IdTable,
+    package: &content::Package,
+    eager_plugins: &HashSet<&str>,
+) -> Component {
     let id = match package {
         content::Package::EagerPlugin(p) => id_table.get(p),
         content::Package::LazyPlugin(p) => id_table.get(p),
         content::Package::LazyGroup(g) => &g.name,
     };
 
-    let is_plugin = match package {
-        content::Package::EagerPlugin(_) => true,
-        content::Package::LazyPlugin(_) => true,
-        _ => false,
-    };
+    let is_plugin = matches!(
+        package,
+        content::Package::EagerPlugin(_) | content::Package::LazyPlugin(_)
+    );
 
     let startup_config = match package {
         content::Package::EagerPlugin(p) => p.startup_config.as_str(),
@@ -49,7 +76,9 @@ fn mk_component<'a>(
                 .depend_plugin_packages
                 .iter()
                 .map(|package| id_table.get(package))
-                .collect::<Vec<&str>>();
+                .filter(|id| !eager_plugins.contains(id))
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>();
             ps.sort();
             ps.dedup();
             ps
@@ -59,7 +88,9 @@ fn mk_component<'a>(
                 .depend_plugin_packages
                 .iter()
                 .map(|package| id_table.get(package))
-                .collect::<Vec<&str>>();
+                .filter(|id| !eager_plugins.contains(id))
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>();
             ps.sort();
             ps.dedup();
             ps
@@ -68,21 +99,13 @@ fn mk_component<'a>(
     let depend_groups = match package {
         content::Package::EagerPlugin(_) => vec![],
         content::Package::LazyPlugin(p) => {
-            let mut ps = p
-                .depend_groups
-                .iter()
-                .map(|p| p.as_str())
-                .collect::<Vec<&str>>();
+            let mut ps = p.depend_groups.clone();
             ps.sort();
             ps.dedup();
             ps
         }
         content::Package::LazyGroup(g) => {
-            let mut ps = g
-                .depend_groups
-                .iter()
-                .map(|p| p.as_str())
-                .collect::<Vec<&str>>();
+            let mut ps = g.depend_groups.clone();
             ps.sort();
             ps.dedup();
             ps
@@ -97,7 +120,9 @@ fn mk_component<'a>(
                 .plugins
                 .iter()
                 .map(|package| id_table.get(package))
-                .collect::<Vec<&str>>();
+                .filter(|id| !eager_plugins.contains(id))
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>();
             ps.sort();
             ps.dedup();
             ps
@@ -105,121 +130,369 @@ fn mk_component<'a>(
     };
 
     Component {
-        id,
+        id: id.to_string(),
         is_plugin,
-        startup_config,
-        pre_config,
-        post_config,
+        startup_config: startup_config.to_string(),
+        pre_config: pre_config.to_string(),
+        post_config: post_config.to_string(),
         depend_plugins,
         depend_groups,
         group_plugins,
     }
 }
 
-fn mk_after_option<'a>(option: &'a content::AfterOption) -> AfterOption<'a> {
+fn mk_after_code(code: &content::AfterCode) -> AfterCode {
+    AfterCode {
+        language: code.language.clone(),
+        code: code.code.clone(),
+    }
+}
+
+fn mk_after_codes(codes: &HashMap<String, content::AfterCode>) -> HashMap<String, AfterCode> {
+    let mut after_codes = HashMap::new();
+    for (k, v) in codes {
+        after_codes.insert(k.clone(), mk_after_code(v));
+    }
+    after_codes
+}
+
+fn mk_after_option(option: &content::AfterOption) -> AfterOption {
+    let mut queries = HashMap::new();
+    for (language, query) in &option.queries {
+        let query = query
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<BTreeMap<_, _>>();
+        queries.insert(language.clone(), query);
+    }
     let mut ftplugin = HashMap::new();
-    for (k, v) in &option.ftplugin {
-        ftplugin.insert(k.as_str(), v.as_str());
+    for (filetype, codes) in &option.ftplugin {
+        ftplugin.insert(
+            filetype.clone(),
+            codes.iter().map(mk_after_code).collect::<Vec<_>>(),
+        );
+    }
+    AfterOption {
+        ftplugin,
+        plugin: mk_after_codes(&option.plugin),
+        syntax: mk_after_codes(&option.syntax),
+        indent: mk_after_codes(&option.indent),
+        lsp: mk_after_codes(&option.lsp),
+        queries,
     }
-    AfterOption { ftplugin }
 }
 
-pub fn bundle<'a>(config: &'a content::Content) -> Bundle<'a> {
+/// only one stub can be defined per command, so those of the same name must agree.
+fn insert_command_stub(
+    stubs: &mut BTreeMap<String, content::CommandStub>,
+    stub: &content::CommandStub,
+) -> Result<()> {
+    match stubs.insert(stub.name.clone(), stub.clone()) {
+        Some(other) if other != *stub => bail!(
+            "conflicting stubs of command `{}`\n{:?}\n{:?}.",
+            stub.name,
+            other,
+            stub
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// find plugins declared both eager and lazy.
+///
+/// fails unless `prefer_eager` is set, in which case the lazy declarations are dropped.
+/// members of a group are declarations too. eager plugins pulled in as a dependency are
+/// already loaded, so they are dropped without a conflict.
+fn resolve_eager_conflicts<'a>(
+    config: &'a content::Content,
+    eager_plugins: &HashSet<&'a str>,
+) -> Result<Vec<&'a content::Package>> {
+    let mut conflicts = config
+        .packages
+        .iter()
+        .filter_map(|package| match package {
+            content::Package::LazyPlugin(p) if !p.is_dependency => Some(config.id_table.get(p)),
+            _ => None,
+        })
+        .filter(|id| eager_plugins.contains(id))
+        .collect::<Vec<&str>>();
+    conflicts.sort();
+    conflicts.dedup();
+
+    if !conflicts.is_empty() && !config.prefer_eager {
+        bail!(
+            "Plugins declared both eager and lazy ({}). Remove one of the declarations or set `preferEager` to load them eagerly.",
+            conflicts.join(", ")
+        )
+    }
+    for id in &conflicts {
+        log::warn!(
+            "`{}` is declared both eager and lazy, lazy declaration is dropped.",
+            id
+        );
+    }
+
+    Ok(config
+        .packages
+        .iter()
+        .filter(|package| match package {
+            content::Package::LazyPlugin(p) => !eager_plugins.contains(config.id_table.get(p)),
+            _ => true,
+        })
+        .collect())
+}
+
+/// lua modules of the plugins, resolved as `require` would once their owners are loaded.
+///
+/// which of several plugins shipping a module wins depends on the runtimepath order, so such
+/// modules are left to the runtimepath search. a module shipped by an eager plugin needs no owner.
+fn mk_lua_modules(
+    plugin_paths: &HashMap<PluginId, PluginPath>,
+    eager_plugins: &HashSet<&str>,
+    groups: &HashMap<&str, Vec<&str>>,
+) -> Result<BTreeMap<String, LuaModule>> {
+    let mut plugins = plugin_paths.iter().collect::<Vec<_>>();
+    plugins.sort();
+    let mut shippers: BTreeMap<String, Vec<(&str, String)>> = BTreeMap::new();
+    for (id, path) in plugins {
+        for (name, file) in find_lua_modules(path)? {
+            shippers.entry(name).or_default().push((id.as_str(), file));
+        }
+    }
+
+    let mut lua_modules = BTreeMap::new();
+    for (name, shippers) in shippers {
+        let file = match &shippers[..] {
+            [(_, file)] => Some(file.clone()),
+            _ => None,
+        };
+        let mut owners = vec![];
+        if !shippers.iter().any(|(id, _)| eager_plugins.contains(id)) {
+            for (id, _) in &shippers {
+                match groups.get(id) {
+                    Some(names) => owners.extend(names.iter().map(|name| name.to_string())),
+                    None => owners.push(id.to_string()),
+                }
+            }
+        }
+        owners.sort();
+        owners.dedup();
+        lua_modules.insert(name, LuaModule { file, owners });
+    }
+
+    Ok(lua_modules)
+}
+
+/// id of the plugin `follower` is loaded after.
+fn find_host<'a>(id_table: &'a content::IdTable, package: &str, follower: &str) -> Result<&'a str> {
+    match id_table.find(package) {
+        Some(host) => Ok(host),
+        None => bail!(
+            "`{}` is loaded after `{}`, which is not in the bundle.",
+            follower,
+            package
+        ),
+    }
+}
+
+/// index the plugins of the content and their load triggers.
+pub fn bundle(config: &content::Content) -> Result<Bundle> {
     let mut components = Vec::new();
     let mut load_option = LoadOption::default();
 
-    for package in &config.packages {
-        components.push(mk_component(&config.id_table, package));
+    let eager_plugins = config
+        .packages
+        .iter()
+        .filter_map(|package| match package {
+            content::Package::EagerPlugin(p) => Some(config.id_table.get(p)),
+            _ => None,
+        })
+        .collect::<HashSet<&str>>();
+    let packages = resolve_eager_conflicts(config, &eager_plugins)?;
+
+    for package in &packages {
+        components.push(mk_component(&config.id_table, package, &eager_plugins));
         match package {
             content::Package::EagerPlugin(p) => {
                 let id = config.id_table.get(p);
-                load_option.plugin_paths.insert(id, p.nix_package.as_str());
+                load_option
+                    .plugin_paths
+                    .insert(id.to_string(), p.nix_package.clone());
                 if !p.startup_config.is_empty() {
-                    load_option.startup_config_plugins.push(id);
+                    load_option.startup_config_plugins.push(id.to_string());
                 }
             }
             content::Package::LazyPlugin(p) => {
                 let id = config.id_table.get(p);
 
-                load_option.plugin_paths.insert(id, p.nix_package.as_str());
+                load_option
+                    .plugin_paths
+                    .insert(id.to_string(), p.nix_package.clone());
 
                 if !p.startup_config.is_empty() {
-                    load_option.startup_config_plugins.push(id);
+                    load_option.startup_config_plugins.push(id.to_string());
                 }
 
                 for module in &p.on_modules {
                     load_option
                         .on_modules
-                        .entry(module.as_str())
+                        .entry(module.clone())
                         .or_default()
-                        .push(id);
+                        .push(id.to_string());
                 }
                 for event in &p.on_events {
                     load_option
                         .on_events
-                        .entry(event.as_str())
+                        .entry(event.clone())
                         .or_default()
-                        .push(id);
+                        .push(id.to_string());
                 }
                 for filetype in &p.on_filetypes {
                     load_option
                         .on_filetypes
-                        .entry(filetype.as_str())
+                        .entry(filetype.clone())
                         .or_default()
-                        .push(id);
+                        .push(id.to_string());
                 }
                 for command in &p.on_commands {
                     load_option
                         .on_commands
-                        .entry(command.as_str())
+                        .entry(command.clone())
+                        .or_default()
+                        .push(id.to_string());
+                }
+                for stub in &p.command_stubs {
+                    insert_command_stub(&mut load_option.command_stubs, stub)?;
+                }
+                for function in &p.on_functions {
+                    load_option
+                        .on_functions
+                        .entry(function.clone())
+                        .or_default()
+                        .push(id.to_string());
+                }
+                for package in &p.on_source_packages {
+                    let host = find_host(&config.id_table, package, id)?;
+                    // eager plugins are never loaded by the loader, so follow them at startup.
+                    if eager_plugins.contains(host) {
+                        load_option.startup_followers.push(id.to_string());
+                    } else {
+                        load_option
+                            .followers
+                            .entry(host.to_string())
+                            .or_default()
+                            .push(id.to_string());
+                    }
+                }
+                for colorscheme in &p.on_colorschemes {
+                    load_option
+                        .on_colorschemes
+                        .entry(colorscheme.clone())
+                        .or_default()
+                        .push(id.to_string());
+                }
+                for marker in &p.on_root_markers {
+                    load_option
+                        .on_root_markers
+                        .entry(marker.clone())
                         .or_default()
-                        .push(id);
+                        .push(id.to_string());
                 }
 
                 if p.is_timer_client {
-                    load_option.timer_clients.push(id);
+                    load_option.timer_clients.push(id.to_string());
                 }
                 if p.is_denops_client {
-                    load_option.denops_clients.push(id);
+                    load_option.denops_clients.push(id.to_string());
+                    load_option
+                        .denops_scripts
+                        .insert(id.to_string(), find_denops_scripts(&p.nix_package)?);
                 }
             }
             content::Package::LazyGroup(g) => {
                 let id = g.name.as_str();
 
                 if !g.startup_config.is_empty() {
-                    load_option.startup_config_plugins.push(id);
+                    load_option.startup_config_plugins.push(id.to_string());
                 }
 
                 for module in &g.on_modules {
                     load_option
                         .on_modules
-                        .entry(module.as_str())
+                        .entry(module.clone())
                         .or_default()
-                        .push(id);
+                        .push(id.to_string());
                 }
                 for event in &g.on_events {
                     load_option
                         .on_events
-                        .entry(event.as_str())
+                        .entry(event.clone())
                         .or_default()
-                        .push(id);
+                        .push(id.to_string());
                 }
                 for filetype in &g.on_filetypes {
                     load_option
                         .on_filetypes
-                        .entry(filetype.as_str())
+                        .entry(filetype.clone())
                         .or_default()
-                        .push(id);
+                        .push(id.to_string());
                 }
                 for command in &g.on_commands {
                     load_option
                         .on_commands
-                        .entry(command.as_str())
+                        .entry(command.clone())
                         .or_default()
-                        .push(id);
+                        .push(id.to_string());
+                }
+                for stub in &g.command_stubs {
+                    insert_command_stub(&mut load_option.command_stubs, stub)?;
+                }
+                for function in &g.on_functions {
+                    load_option
+                        .on_functions
+                        .entry(function.clone())
+                        .or_default()
+                        .push(id.to_string());
+                }
+                for package in &g.on_source_packages {
+                    let host = find_host(&config.id_table, package, id)?;
+                    // eager plugins are never loaded by the loader, so follow them at startup.
+                    if eager_plugins.contains(host) {
+                        load_option.startup_followers.push(id.to_string());
+                    } else {
+                        load_option
+                            .followers
+                            .entry(host.to_string())
+                            .or_default()
+                            .push(id.to_string());
+                    }
+                }
+                for colorscheme in &g.on_colorschemes {
+                    load_option
+                        .on_colorschemes
+                        .entry(colorscheme.clone())
+                        .or_default()
+                        .push(id.to_string());
+                }
+                for marker in &g.on_root_markers {
+                    load_option
+                        .on_root_markers
+                        .entry(marker.clone())
+                        .or_default()
+                        .push(id.to_string());
                 }
                 if g.is_timer_client {
-                    load_option.timer_clients.push(id);
+                    load_option.timer_clients.push(id.to_string());
+                }
+                if g.is_denops_client {
+                    let mut scripts = vec![];
+                    for package in &g.plugins {
+                        scripts.extend(find_denops_scripts(package)?);
+                    }
+                    scripts.sort();
+                    scripts.dedup();
+                    load_option.denops_clients.push(id.to_string());
+                    load_option.denops_scripts.insert(id.to_string(), scripts);
                 }
             }
         }
@@ -241,39 +514,690 @@ pub fn bundle<'a>(config: &'a content::Content) -> Bundle<'a> {
         plugins.sort();
         plugins.dedup();
     }
+    for plugins in load_option.on_functions.values_mut() {
+        plugins.sort();
+        plugins.dedup();
+    }
+    for plugins in load_option.followers.values_mut() {
+        plugins.sort();
+        plugins.dedup();
+    }
+    load_option.startup_followers.sort();
+    load_option.startup_followers.dedup();
+    for plugins in load_option.on_colorschemes.values_mut() {
+        plugins.sort();
+        plugins.dedup();
+    }
+    for plugins in load_option.on_root_markers.values_mut() {
+        plugins.sort();
+        plugins.dedup();
+    }
     load_option.startup_config_plugins.sort();
     load_option.startup_config_plugins.dedup();
     load_option.timer_clients.sort();
     load_option.timer_clients.dedup();
     load_option.denops_clients.sort();
     load_option.denops_clients.dedup();
+    for (id, scripts) in &load_option.denops_scripts {
+        if scripts.is_empty() {
+            log::warn!("no denops script found in `{}`.", id);
+        }
+    }
+
+    let mut lazy_plugins = load_option
+        .plugin_paths
+        .iter()
+        .filter(|(id, _)| !eager_plugins.contains(id.as_str()))
+        .collect::<Vec<_>>();
+    lazy_plugins.sort();
+    for (id, path) in lazy_plugins {
+        for script in find_ftdetect_scripts(path)? {
+            load_option.ftdetect_scripts.push((id.clone(), script));
+        }
+    }
 
-    let components = merge_vector(components).unwrap();
+    // help tags of flattened eager plugins are written with the eager runtime.
+    let mut help_plugins = load_option
+        .plugin_paths
+        .iter()
+        .filter(|(id, _)| !(config.flatten_eager && eager_plugins.contains(id.as_str())))
+        .collect::<Vec<_>>();
+    help_plugins.sort();
+    for (id, path) in help_plugins {
+        let plugin = if eager_plugins.contains(id.as_str()) {
+            None
+        } else {
+            Some(id.clone())
+        };
+        for (tag, file) in find_help_tags(path)? {
+            load_option.help_tags.push(HelpTag {
+                tag,
+                file,
+                plugin: plugin.clone(),
+            });
+        }
+    }
+    load_option.help_tags.sort();
 
-    Bundle {
+    // members are loaded through their groups, so that the group configs run.
+    let mut groups: HashMap<&str, Vec<&str>> = HashMap::new();
+    for package in &packages {
+        if let content::Package::LazyGroup(g) = package {
+            for plugin in &g.plugins {
+                groups
+                    .entry(config.id_table.get(plugin))
+                    .or_default()
+                    .push(g.name.as_str());
+            }
+        }
+    }
+    load_option.lua_modules = mk_lua_modules(&load_option.plugin_paths, &eager_plugins, &groups)?;
+
+    let eager_runtime = if config.flatten_eager {
+        let mut plugins = load_option
+            .plugin_paths
+            .iter()
+            .filter(|(id, _)| eager_plugins.contains(id.as_str()))
+            .map(|(id, path)| (id.clone(), path.clone()))
+            .collect::<Vec<_>>();
+        plugins.sort();
+        let mut help_tags = vec![];
+        for (_, path) in &plugins {
+            for (tag, file) in find_help_tags(path)? {
+                help_tags.push(HelpTag {
+                    tag,
+                    file,
+                    plugin: None,
+                });
+            }
+        }
+        help_tags.sort();
+        Some(EagerRuntime { plugins, help_tags })
+    } else {
+        None
+    };
+
+    if config.collisions != content::Collisions::Off {
+        let collisions = find_runtime_collisions(&load_option.plugin_paths)?;
+        for (path, ids) in &collisions {
+            log::warn!(
+                "`{}` is shipped by multiple plugins ({}).",
+                path,
+                ids.join(", ")
+            );
+        }
+        if config.collisions == content::Collisions::Error && !collisions.is_empty() {
+            bail!(
+                "Runtime file collisions found ({} files). Set `collisions` to `warn` to allow them.",
+                collisions.len()
+            )
+        }
+    }
+
+    let mut components = merge_vector(components)?;
+    // a stable order, so that an archive only changes with the bundle.
+    components.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(Bundle {
+        startup_config: config.startup_config.clone(),
         components,
         load_option,
         after_option: mk_after_option(&config.after_option),
+        eager_runtime,
         info: Info {
-            bundler_bin: config.info.bundler_bin.as_str(),
+            bundler_bin: config.info.bundler_bin.clone(),
         },
+    })
+}
+
+/// `root_dir` made absolute, so its siblings can be found.
+fn absolute_dir(root_dir: &str) -> Result<PathBuf> {
+    let root_dir = Path::new(root_dir);
+    let root_dir = if root_dir.is_absolute() {
+        root_dir.to_path_buf()
+    } else {
+        env::current_dir()?.join(root_dir)
+    };
+    if root_dir.file_name().is_none() {
+        bail!("invalid output dir `{}`.", root_dir.display());
     }
+    Ok(root_dir)
 }
 
-pub fn export<'a>(bundle: Bundle<'a>, export_option: ExportOption<'a>) -> Result<()> {
-    // components
-    for component in bundle.components {
-        component.export_file(&export_option)?;
+/// name prefix of the directories built next to `root_dir`.
+fn staging_prefix(root_dir: &Path) -> String {
+    let name = root_dir.file_name().unwrap_or_default();
+    format!(".{}.bundler-", name.to_string_lossy())
+}
+
+/// a fresh directory next to `root_dir` to build the export in.
+fn staging_dir(root_dir: &Path) -> Result<PathBuf> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    Ok(root_dir.with_file_name(format!(
+        "{}{}-{}",
+        staging_prefix(root_dir),
+        process::id(),
+        nanos
+    )))
+}
+
+/// remove directories next to `root_dir` left by previous exports, except the one
+/// `root_dir` links to.
+fn remove_stale_dirs(root_dir: &Path) -> Result<()> {
+    let prefix = staging_prefix(root_dir);
+    let current = fs::read_link(root_dir).ok();
+    let parent_dir = root_dir.parent().unwrap_or(Path::new("/"));
+    for (name, path) in read_dir_entries(parent_dir)? {
+        if name.starts_with(&prefix) && current.as_deref() != Some(Path::new(&name)) {
+            fs::remove_dir_all(path)?;
+        }
     }
+    Ok(())
+}
 
-    // load options
-    bundle.load_option.export_file(&export_option)?;
+/// whether `root_dir` holds a previous export.
+fn is_export_dir(root_dir: &Path) -> bool {
+    // `info/bundler_bin` marks exports made before the stamp was introduced.
+    root_dir.join(STAMP).exists() || root_dir.join(INFO).join(BUNDLER_BIN).exists()
+}
 
-    // after options
-    bundle.after_option.export_file(&export_option)?;
+/// export the bundle into `root_dir` with `backend`.
+///
+/// an empty `root_dir` not created by bundler (e.g. `$out` of a nix build) is written in
+/// place. otherwise the bundle is built in a sibling directory: if `root_dir` is missing
+/// it is renamed into place, if it holds a previous export `root_dir` becomes a symlink
+/// to it and is swapped atomically. files that have not changed keep their inode and
+/// mtime.
+pub fn export(
+    bundle: Bundle,
+    backend: &dyn Backend,
+    export_option: ExportOption,
+) -> Result<SyncStats> {
+    let root_dir = absolute_dir(&export_option.root_dir)?;
 
-    // info
-    bundle.info.export_file(&export_option)?;
+    let stats = if root_dir.exists() && !is_export_dir(&root_dir) {
+        // refuse to replace a directory that bundler did not create.
+        if !read_entries(&root_dir)?.is_empty() {
+            bail!(
+                "`{}` is not empty and was not created by bundler.",
+                export_option.root_dir
+            );
+        }
+        // e.g. `$out` made by a nix builder, which must stay a directory in place.
+        export_files(
+            bundle,
+            backend,
+            &mut DirOutput {
+                root_dir: root_dir.clone(),
+            },
+        )?;
+        SyncStats {
+            written: read_entries(&root_dir)?.len(),
+            ..Default::default()
+        }
+    } else {
+        export_staged(bundle, backend, &root_dir, export_option.prune)?
+    };
+    log::info!(
+        "{} files written, {} unchanged, {} deleted",
+        stats.written,
+        stats.unchanged,
+        stats.deleted
+    );
+
+    Ok(stats)
+}
+
+/// build the export next to `root_dir` and move it into place.
+///
+/// a previous export is replaced by pointing the `root_dir` symlink at the new tree.
+fn export_staged(
+    bundle: Bundle,
+    backend: &dyn Backend,
+    root_dir: &Path,
+    prune: bool,
+) -> Result<SyncStats> {
+    remove_stale_dirs(root_dir)?;
+    let staging_dir = staging_dir(root_dir)?;
+    let mut out = DirOutput {
+        root_dir: staging_dir.clone(),
+    };
+    let result = export_files(bundle, backend, &mut out).and_then(|_| {
+        if !root_dir.exists() {
+            let written = read_entries(&staging_dir)?.len();
+            fs::rename(&staging_dir, root_dir)?;
+            return Ok(SyncStats {
+                written,
+                ..Default::default()
+            });
+        }
+        let stats = reuse_unchanged(&staging_dir, root_dir, prune)?;
+        swap_link(&staging_dir, root_dir)?;
+        Ok(stats)
+    });
+    // drops the previous tree, or the staging directory if the export failed.
+    remove_stale_dirs(root_dir)?;
+    result
+}
+
+/// export the bundle with `backend` as a tar archive written to `writer`.
+///
+/// entries are streamed into the archive as the backend writes them.
+pub fn export_tar<W: Write>(bundle: Bundle, backend: &dyn Backend, writer: W) -> Result<()> {
+    let mut out = TarOutput::new(writer);
+    export_files(bundle, backend, &mut out)?;
+    out.finish()?;
 
     Ok(())
 }
+
+fn export_files(bundle: Bundle, backend: &dyn Backend, out: &mut dyn Output) -> Result<()> {
+    backend.export(bundle, out)?;
+    out.write_file(STAMP, env!("CARGO_PKG_VERSION"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload;
+    use crate::util::temp::TempDir;
+    use rstest::rstest;
+
+    fn as_strs(index: &HashMap<String, Vec<String>>) -> HashMap<&str, Vec<&str>> {
+        index
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.iter().map(|s| s.as_str()).collect()))
+            .collect()
+    }
+
+    fn mk_content(prefer_eager: bool) -> content::Content {
+        let id_map = ["/nix/store/foo", "/nix/store/bar"]
+            .iter()
+            .zip(["foo", "bar"])
+            .map(|(package, id)| payload::IdMapElement {
+                plugin_id: id.to_string(),
+                package: package.to_string(),
+            })
+            .collect::<Vec<_>>();
+        content::Content {
+            startup_config: String::default(),
+            packages: vec![
+                content::Package::EagerPlugin(content::EagerPlugin {
+                    nix_package: "/nix/store/foo".to_string(),
+                    ..Default::default()
+                }),
+                content::Package::LazyPlugin(content::LazyPlugin {
+                    nix_package: "/nix/store/foo".to_string(),
+                    on_commands: vec!["Foo".to_string()],
+                    ..Default::default()
+                }),
+                content::Package::LazyGroup(content::LazyGroup {
+                    name: "group".to_string(),
+                    plugins: vec!["/nix/store/foo".to_string(), "/nix/store/bar".to_string()],
+                    ..Default::default()
+                }),
+            ],
+            id_table: content::IdTable::from(id_map),
+            after_option: content::AfterOption::default(),
+            info: content::Info {
+                bundler_bin: String::default(),
+            },
+            prefer_eager,
+            collisions: content::Collisions::Off,
+            flatten_eager: false,
+        }
+    }
+
+    #[test]
+    fn test_bundle_eager_lazy_conflict() {
+        let content = mk_content(false);
+
+        assert!(bundle(&content).is_err());
+    }
+
+    #[test]
+    fn test_bundle_eager_dependency() {
+        let mut content = mk_content(false);
+        content.packages = vec![
+            content::Package::EagerPlugin(content::EagerPlugin {
+                nix_package: "/nix/store/foo".to_string(),
+                ..Default::default()
+            }),
+            content::Package::LazyPlugin(content::LazyPlugin {
+                nix_package: "/nix/store/bar".to_string(),
+                depend_plugin_packages: vec!["/nix/store/foo".to_string()],
+                ..Default::default()
+            }),
+            content::Package::LazyPlugin(content::LazyPlugin {
+                nix_package: "/nix/store/foo".to_string(),
+                is_dependency: true,
+                ..Default::default()
+            }),
+        ];
+
+        let act = bundle(&content).unwrap();
+
+        let mut ids = act.components.iter().map(|c| &c.id).collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(vec!["bar", "foo"], ids);
+    }
+
+    #[rstest(flatten_eager, exp,
+        case(false, vec![("bar", Some("bar")), ("foo", None)]),
+        case(true, vec![("bar", Some("bar"))]),
+    )]
+    fn test_bundle_help_tags(flatten_eager: bool, exp: Vec<(&str, Option<&str>)>) {
+        let root = TempDir::new("help");
+        root.write("foo/doc/foo.txt", "*foo*");
+        root.write("bar/doc/bar.txt", "*bar*");
+        let (foo, bar) = (root.join("foo"), root.join("bar"));
+        let (foo, bar) = (foo.to_str().unwrap(), bar.to_str().unwrap());
+        let mut content = mk_content(false);
+        content.id_table = content::IdTable::from(
+            [(foo, "foo"), (bar, "bar")]
+                .iter()
+                .map(|(package, id)| payload::IdMapElement {
+                    plugin_id: id.to_string(),
+                    package: package.to_string(),
+                })
+                .collect::<Vec<_>>(),
+        );
+        content.packages = vec![
+            content::Package::EagerPlugin(content::EagerPlugin {
+                nix_package: foo.to_string(),
+                ..Default::default()
+            }),
+            content::Package::LazyPlugin(content::LazyPlugin {
+                nix_package: bar.to_string(),
+                ..Default::default()
+            }),
+        ];
+        content.flatten_eager = flatten_eager;
+
+        let act = bundle(&content).unwrap();
+
+        let act = act
+            .load_option
+            .help_tags
+            .iter()
+            .map(|help_tag| (help_tag.tag.as_str(), help_tag.plugin.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(exp, act);
+    }
+
+    #[test]
+    fn test_mk_lua_modules() {
+        let root = TempDir::new("lua-modules");
+        for file in [
+            "foo/lua/foo.lua",
+            "foo/lua/shared.lua",
+            "bar/lua/bar/init.lua",
+            "bar/lua/shared.lua",
+            "baz/lua/baz.lua",
+            "baz/lua/dup.lua",
+            "qux/lua/dup.lua",
+        ] {
+            root.write(file, "");
+        }
+        let paths =
+            ["foo", "bar", "baz", "qux"].map(|p| root.join(p).to_string_lossy().to_string());
+        let plugin_paths = ["foo", "bar", "baz", "qux"]
+            .into_iter()
+            .map(str::to_string)
+            .zip(paths)
+            .collect::<HashMap<_, _>>();
+        let eager_plugins = HashSet::from(["foo"]);
+        let groups = HashMap::from([("bar", vec!["group"])]);
+
+        let act = mk_lua_modules(&plugin_paths, &eager_plugins, &groups).unwrap();
+
+        let file = |path: &str| Some(root.join(path).to_string_lossy().to_string());
+        let exp = BTreeMap::from([
+            // a lazy member is loaded through its group.
+            (
+                "bar".to_string(),
+                (file("bar/lua/bar/init.lua"), vec!["group"]),
+            ),
+            ("baz".to_string(), (file("baz/lua/baz.lua"), vec!["baz"])),
+            // shipped by several lazy plugins, which all load before the search.
+            ("dup".to_string(), (None, vec!["baz", "qux"])),
+            ("foo".to_string(), (file("foo/lua/foo.lua"), vec![])),
+            // an eager plugin ships it, so nothing needs loading.
+            ("shared".to_string(), (None, vec![])),
+        ]);
+        let act = act
+            .iter()
+            .map(|(name, module)| {
+                let owners = module.owners.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+                (name.clone(), (module.file.clone(), owners))
+            })
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(exp, act);
+    }
+
+    #[rstest(prefer_eager, case(true), case(false))]
+    fn test_bundle_eager_group_member(prefer_eager: bool) {
+        let mut content = mk_content(prefer_eager);
+        content.packages = vec![
+            content::Package::EagerPlugin(content::EagerPlugin {
+                nix_package: "/nix/store/foo".to_string(),
+                ..Default::default()
+            }),
+            content::Package::LazyGroup(content::LazyGroup {
+                name: "group".to_string(),
+                plugins: vec!["/nix/store/foo".to_string(), "/nix/store/bar".to_string()],
+                ..Default::default()
+            }),
+            content::Package::LazyPlugin(content::LazyPlugin {
+                nix_package: "/nix/store/foo".to_string(),
+                ..Default::default()
+            }),
+            content::Package::LazyPlugin(content::LazyPlugin {
+                nix_package: "/nix/store/bar".to_string(),
+                ..Default::default()
+            }),
+        ];
+
+        let act = bundle(&content);
+
+        match prefer_eager {
+            true => {
+                let act = act.unwrap();
+                let group = act.components.iter().find(|c| c.id == "group").unwrap();
+                assert_eq!(vec!["bar"], group.group_plugins);
+            }
+            false => assert!(act.is_err()),
+        }
+    }
+
+    #[test]
+    fn test_bundle_prefer_eager() {
+        let content = mk_content(true);
+        let act = bundle(&content).unwrap();
+
+        assert!(act.load_option.on_commands.is_empty());
+        let group = act.components.iter().find(|c| c.id == "group").unwrap();
+        assert_eq!(vec!["bar"], group.group_plugins);
+    }
+
+    #[test]
+    fn test_bundle_on_functions() {
+        let mut content = mk_content(false);
+        content.packages = vec![
+            content::Package::LazyPlugin(content::LazyPlugin {
+                nix_package: "/nix/store/bar".to_string(),
+                on_functions: vec!["fzf#*".to_string()],
+                ..Default::default()
+            }),
+            content::Package::LazyGroup(content::LazyGroup {
+                name: "group".to_string(),
+                plugins: vec!["/nix/store/foo".to_string()],
+                on_functions: vec!["fzf#*".to_string(), "fzf#*".to_string()],
+                ..Default::default()
+            }),
+        ];
+
+        let act = bundle(&content).unwrap();
+
+        assert_eq!(
+            HashMap::from([("fzf#*", vec!["bar", "group"])]),
+            as_strs(&act.load_option.on_functions)
+        );
+    }
+
+    #[test]
+    fn test_bundle_on_colorschemes() {
+        let mut content = mk_content(false);
+        content.packages = vec![
+            content::Package::LazyPlugin(content::LazyPlugin {
+                nix_package: "/nix/store/bar".to_string(),
+                on_colorschemes: vec!["tokyonight".to_string(), "tokyonight-night".to_string()],
+                ..Default::default()
+            }),
+            content::Package::LazyGroup(content::LazyGroup {
+                name: "group".to_string(),
+                plugins: vec!["/nix/store/foo".to_string()],
+                on_colorschemes: vec!["tokyonight".to_string(), "tokyonight".to_string()],
+                ..Default::default()
+            }),
+        ];
+
+        let act = bundle(&content).unwrap();
+
+        assert_eq!(
+            HashMap::from([
+                ("tokyonight", vec!["bar", "group"]),
+                ("tokyonight-night", vec!["bar"])
+            ]),
+            as_strs(&act.load_option.on_colorschemes)
+        );
+    }
+
+    #[test]
+    fn test_bundle_on_root_markers() {
+        let mut content = mk_content(false);
+        content.packages = vec![
+            content::Package::LazyPlugin(content::LazyPlugin {
+                nix_package: "/nix/store/bar".to_string(),
+                on_root_markers: vec!["Cargo.toml".to_string(), ".git/config".to_string()],
+                ..Default::default()
+            }),
+            content::Package::LazyGroup(content::LazyGroup {
+                name: "group".to_string(),
+                plugins: vec!["/nix/store/foo".to_string()],
+                on_root_markers: vec![".git/config".to_string()],
+                ..Default::default()
+            }),
+        ];
+
+        let act = bundle(&content).unwrap();
+
+        // markers are kept as configured, so the loader can look them up by path.
+        assert_eq!(
+            HashMap::from([
+                ("Cargo.toml", vec!["bar"]),
+                (".git/config", vec!["bar", "group"])
+            ]),
+            as_strs(&act.load_option.on_root_markers)
+        );
+    }
+
+    #[rstest(host, followers, startup_followers,
+        case("/nix/store/bar", Some(vec!["group"]), vec![]),
+        case("/nix/store/foo", None, vec!["group"]),
+    )]
+    fn test_bundle_followers(
+        host: &str,
+        followers: Option<Vec<&str>>,
+        startup_followers: Vec<&str>,
+    ) {
+        let mut content = mk_content(false);
+        content.packages = vec![
+            content::Package::EagerPlugin(content::EagerPlugin {
+                nix_package: "/nix/store/foo".to_string(),
+                ..Default::default()
+            }),
+            content::Package::LazyPlugin(content::LazyPlugin {
+                nix_package: "/nix/store/bar".to_string(),
+                ..Default::default()
+            }),
+            content::Package::LazyGroup(content::LazyGroup {
+                name: "group".to_string(),
+                on_source_packages: vec![host.to_string()],
+                ..Default::default()
+            }),
+        ];
+
+        let act = bundle(&content).unwrap();
+
+        assert_eq!(
+            HashMap::from_iter(followers.map(|f| ("bar", f))),
+            as_strs(&act.load_option.followers)
+        );
+        assert_eq!(startup_followers, act.load_option.startup_followers);
+    }
+
+    #[test]
+    fn test_bundle_followers_missing_host() {
+        let mut content = mk_content(false);
+        content.packages = vec![content::Package::LazyGroup(content::LazyGroup {
+            name: "group".to_string(),
+            on_source_packages: vec!["/nix/store/baz".to_string()],
+            ..Default::default()
+        })];
+
+        assert!(bundle(&content).is_err());
+    }
+
+    #[test]
+    fn test_export() {
+        let root = TempDir::new("export");
+        let root_dir = root.join("out");
+        let content = mk_content(true);
+        let export_option = || ExportOption {
+            root_dir: root_dir.to_string_lossy().to_string(),
+            prune: true,
+        };
+
+        // built next to the missing `out` and renamed into place.
+        let first = export(bundle(&content).unwrap(), &JsonBackend, export_option()).unwrap();
+        let is_dir = fs::symlink_metadata(&root_dir).unwrap().is_dir();
+        // swapped in as a symlink, twice to drop the previous tree.
+        export(bundle(&content).unwrap(), &JsonBackend, export_option()).unwrap();
+        let last = export(bundle(&content).unwrap(), &JsonBackend, export_option()).unwrap();
+        let is_symlink = root_dir.is_symlink();
+        let entries = read_dir_entries(root.path())
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+
+        assert!(is_dir && is_symlink);
+        assert_eq!(first.written, last.unchanged);
+        assert!(root_dir.join(STAMP).exists());
+        assert_eq!(2, entries.len());
+        assert!(entries[0].starts_with(".out.bundler-"));
+        assert_eq!(fs::read_link(&root_dir).unwrap(), Path::new(&entries[0]));
+    }
+
+    #[test]
+    fn test_export_empty_dir() {
+        let root = TempDir::new("export");
+        let root_dir = root.join("out");
+        fs::create_dir(&root_dir).unwrap();
+        let content = mk_content(true);
+        let export_option = || ExportOption {
+            root_dir: root_dir.to_string_lossy().to_string(),
+            prune: true,
+        };
+
+        // written into the directory, as `mkdir $out` of a nix builder leaves it.
+        export(bundle(&content).unwrap(), &JsonBackend, export_option()).unwrap();
+        let is_dir = fs::symlink_metadata(&root_dir).unwrap().is_dir();
+        let entries = read_dir_entries(root.path()).unwrap();
+
+        assert!(is_dir);
+        assert!(root_dir.join(STAMP).exists());
+        assert_eq!(1, entries.len());
+    }
+}
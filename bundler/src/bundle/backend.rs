@@ -0,0 +1,172 @@
+mod json;
+mod lua;
+mod lua_module;
+mod vim;
+
+pub use crate::bundle::backend::json::JsonBackend;
+pub use crate::bundle::backend::lua::LuaBackend;
+pub use crate::bundle::backend::lua_module::LuaModuleBackend;
+pub use crate::bundle::backend::vim::VimBackend;
+use crate::bundle::export::Exporter;
+use crate::bundle::{Bundle, Output, PluginId, PluginPath};
+use crate::constant::file::STARTUP_CONFIG;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+
+/// data read by the loader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// `nil` or `v:null`.
+    Nil,
+    /// `true` or `v:true`.
+    Bool(bool),
+    /// integer.
+    Number(i64),
+    /// string literal.
+    Str(String),
+    /// list or array table.
+    List(Vec<Value>),
+    /// entries in the order they are written.
+    Dict(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// list of strings.
+    pub fn strs<S: AsRef<str>>(v: &[S]) -> Self {
+        Value::List(
+            v.iter()
+                .map(|s| Value::Str(s.as_ref().to_string()))
+                .collect(),
+        )
+    }
+
+    /// dictionary of strings.
+    pub fn str_dict<K: AsRef<str>, V: AsRef<str>>(v: &[(K, V)]) -> Self {
+        Value::Dict(
+            v.iter()
+                .map(|(k, v)| (k.as_ref().to_string(), Value::Str(v.as_ref().to_string())))
+                .collect(),
+        )
+    }
+
+    /// dictionary of flags set to true.
+    pub fn flags<S: AsRef<str>>(v: &[S]) -> Self {
+        Value::Dict(
+            v.iter()
+                .map(|s| (s.as_ref().to_string(), Value::Bool(true)))
+                .collect(),
+        )
+    }
+}
+
+/// receives the bundle flattened into keys such as `command_keys` or `pre_config/<id>`.
+pub trait Sink {
+    /// data read by the loader.
+    fn write_value(&mut self, key: &str, value: Value) -> Result<()>;
+
+    /// code run by the loader.
+    fn write_code(&mut self, key: &str, code: &str) -> Result<()>;
+
+    /// a file looked up through runtimepath, e.g. `after/ftplugin/nix.vim`.
+    fn write_file(&mut self, path: &str, content: &str) -> Result<()>;
+
+    /// a directory merging the runtime files of `plugins`.
+    fn link_runtime(&mut self, path: &str, plugins: &[(PluginId, PluginPath)]) -> Result<()>;
+
+    /// called once everything has been written.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// an output format of the bundle.
+pub trait Backend {
+    /// write the bundle into `out`.
+    fn export(&self, bundle: Bundle, out: &mut dyn Output) -> Result<()>;
+}
+
+/// write the whole bundle into `sink`.
+pub fn write_bundle(bundle: Bundle, sink: &mut dyn Sink) -> Result<()> {
+    // startup config
+    sink.write_code(STARTUP_CONFIG, &bundle.startup_config)?;
+
+    // components
+    for component in bundle.components {
+        component.export(sink)?;
+    }
+
+    // load options
+    bundle.load_option.export(sink)?;
+
+    // after options
+    bundle.after_option.export(sink)?;
+
+    // eager runtime
+    if let Some(eager_runtime) = bundle.eager_runtime {
+        eager_runtime.export(sink)?;
+    }
+
+    // info
+    bundle.info.export(sink)?;
+
+    sink.finish()
+}
+
+/// backends selectable by name, e.g. with `--format`.
+pub struct Registry {
+    backends: BTreeMap<String, Box<dyn Backend>>,
+}
+
+impl Registry {
+    /// add a backend, replacing the one registered with the same name.
+    pub fn register(&mut self, name: &str, backend: Box<dyn Backend>) {
+        self.backends.insert(name.to_string(), backend);
+    }
+
+    /// the backend registered as `name`, fails listing the names otherwise.
+    pub fn get(&self, name: &str) -> Result<&dyn Backend> {
+        self.backends
+            .get(name)
+            .map(|backend| backend.as_ref())
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown format `{}` (available: {}).",
+                    name,
+                    self.names().join(", ")
+                )
+            })
+    }
+
+    /// registered names, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        self.backends.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+impl Default for Registry {
+    /// the built-in backends.
+    fn default() -> Self {
+        let mut registry = Registry {
+            backends: BTreeMap::new(),
+        };
+        registry.register("lua", Box::new(LuaBackend));
+        registry.register("lua-module", Box::new(LuaModuleBackend));
+        registry.register("vim", Box::new(VimBackend));
+        registry.register("json", Box::new(JsonBackend));
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let registry = Registry::default();
+
+        assert_eq!(vec!["json", "lua", "lua-module", "vim"], registry.names());
+        assert!(registry.get("lua").is_ok());
+        assert!(registry.get("toml").is_err());
+    }
+}
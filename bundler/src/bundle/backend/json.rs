@@ -0,0 +1,71 @@
+use crate::bundle::backend::Backend;
+use crate::bundle::{Bundle, Output};
+use crate::constant::file::JSON;
+use anyhow::Result;
+
+/// the resolved bundle as a single `bundle.json`, for tools outside of vim.
+///
+/// maps are written with sorted keys, so the document only changes with the bundle.
+pub struct JsonBackend;
+
+impl Backend for JsonBackend {
+    fn export(&self, bundle: Bundle, out: &mut dyn Output) -> Result<()> {
+        out.write_file(JSON, &serde_json::to_string_pretty(&bundle)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::{AfterOption, Component, DirOutput, Info, LoadOption};
+    use crate::util::temp::TempDir;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_json_backend() {
+        let bundle = Bundle {
+            startup_config: String::new(),
+            components: vec![Component {
+                id: "foo".to_string(),
+                is_plugin: true,
+                post_config: "echo 'foo'".to_string(),
+                depend_plugins: vec!["bar".to_string()],
+                ..Default::default()
+            }],
+            load_option: LoadOption {
+                on_commands: HashMap::from([
+                    ("Foo".to_string(), vec!["foo".to_string()]),
+                    ("Bar".to_string(), vec!["foo".to_string()]),
+                ]),
+                ..Default::default()
+            },
+            after_option: AfterOption {
+                ftplugin: HashMap::new(),
+                plugin: HashMap::new(),
+                syntax: HashMap::new(),
+                indent: HashMap::new(),
+                lsp: HashMap::new(),
+                queries: HashMap::new(),
+            },
+            eager_runtime: None,
+            info: Info {
+                bundler_bin: "/bin/bundler".to_string(),
+            },
+        };
+        let root_dir = TempDir::new("json");
+        let mut out = DirOutput {
+            root_dir: root_dir.path().to_path_buf(),
+        };
+
+        JsonBackend.export(bundle, &mut out).unwrap();
+        let text = std::fs::read_to_string(root_dir.join(JSON)).unwrap();
+        let act = serde_json::from_str::<serde_json::Value>(&text).unwrap();
+
+        assert_eq!(json!("echo 'foo'"), act["components"][0]["postConfig"]);
+        assert_eq!(json!(["bar"]), act["components"][0]["dependPlugins"]);
+        assert_eq!(json!(null), act["eagerRuntime"]);
+        assert_eq!(json!("/bin/bundler"), act["info"]["bundlerBin"]);
+        assert!(text.find("\"Bar\"").unwrap() < text.find("\"Foo\"").unwrap());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::bundle::backend::{write_bundle, Backend, Sink, Value};
+use crate::bundle::flatten::link_runtime;
+use crate::bundle::{Bundle, Output, PluginId, PluginPath};
+use crate::util::lua::to_lua_string;
+use anyhow::Result;
+
+/// value to lua expression.
+pub fn to_lua_value(value: &Value) -> String {
+    match value {
+        Value::Nil => "nil".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Str(s) => to_lua_string(s),
+        Value::List(v) => {
+            let items = v.iter().map(to_lua_value).collect::<Vec<_>>();
+            format!("{{{}}}", items.join(","))
+        }
+        Value::Dict(v) => {
+            let items = v
+                .iter()
+                .map(|(k, v)| format!("[{}]={}", to_lua_string(k), to_lua_value(v)))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", items.join(","))
+        }
+    }
+}
+
+/// one lua file per key, each returning its value.
+struct LuaSink<'o> {
+    out: &'o mut dyn Output,
+}
+
+impl<'o> Sink for LuaSink<'o> {
+    fn write_value(&mut self, key: &str, value: Value) -> Result<()> {
+        self.out
+            .write_file(key, &format!("return {}", to_lua_value(&value)))
+    }
+
+    fn write_code(&mut self, key: &str, code: &str) -> Result<()> {
+        self.out.write_file(key, code)
+    }
+
+    fn write_file(&mut self, path: &str, content: &str) -> Result<()> {
+        self.out.write_file(path, content)
+    }
+
+    fn link_runtime(&mut self, path: &str, plugins: &[(PluginId, PluginPath)]) -> Result<()> {
+        link_runtime(self.out, path, plugins)
+    }
+}
+
+/// the layout read by bundler-nvim, one `dofile`-able lua file per key.
+pub struct LuaBackend;
+
+impl Backend for LuaBackend {
+    fn export(&self, bundle: Bundle, out: &mut dyn Output) -> Result<()> {
+        write_bundle(bundle, &mut LuaSink { out })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(arg, exp,
+        case(Value::Nil, "nil"),
+        case(Value::Number(-1), "-1"),
+        case(Value::Str("a\"b".to_string()), r#""a\"b""#),
+        case(Value::strs::<&str>(&[]), r#"{}"#),
+        case(Value::strs(&["a", "b"]), r#"{"a","b"}"#),
+        case(Value::flags(&["a", "b"]), r#"{["a"]=true,["b"]=true}"#),
+        case(Value::str_dict(&[("a", "b"), ("c\"", "d")]), r#"{["a"]="b",["c\""]="d"}"#),
+        case(Value::Dict(vec![("a".to_string(), Value::strs(&["b"]))]), r#"{["a"]={"b"}}"#),
+    )]
+    fn test_to_lua_value(arg: Value, exp: &str) {
+        let act = to_lua_value(&arg);
+
+        assert_eq!(exp, act);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::bundle::backend::lua::to_lua_value;
+use crate::bundle::backend::{write_bundle, Backend, Sink, Value};
+use crate::bundle::flatten::link_runtime;
+use crate::bundle::{Bundle, Output, PluginId, PluginPath};
+use crate::constant::file::LUA_MODULE;
+use crate::util::lua::to_lua_string;
+use anyhow::Result;
+
+/// values and codes gathered into one lua file.
+struct LuaModuleSink<'o> {
+    out: &'o mut dyn Output,
+    values: Vec<(String, String)>,
+    codes: Vec<(String, String)>,
+}
+
+/// `[key]=expr,` lines sorted by key.
+fn mk_entries(entries: &mut [(String, String)]) -> String {
+    entries.sort();
+    entries
+        .iter()
+        .map(|(key, expr)| format!("[{}]={},", to_lua_string(key), expr))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl<'o> Sink for LuaModuleSink<'o> {
+    fn write_value(&mut self, key: &str, value: Value) -> Result<()> {
+        self.values.push((key.to_string(), to_lua_value(&value)));
+        Ok(())
+    }
+
+    // kept as strings, so that an error in one config does not break the module.
+    fn write_code(&mut self, key: &str, code: &str) -> Result<()> {
+        self.codes.push((key.to_string(), to_lua_string(code)));
+        Ok(())
+    }
+
+    fn write_file(&mut self, path: &str, content: &str) -> Result<()> {
+        self.out.write_file(path, content)
+    }
+
+    fn link_runtime(&mut self, path: &str, plugins: &[(PluginId, PluginPath)]) -> Result<()> {
+        link_runtime(self.out, path, plugins)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let module = format!(
+            "return {{\nvalues={{\n{}\n}},\ncodes={{\n{}\n}},\n}}\n",
+            mk_entries(&mut self.values),
+            mk_entries(&mut self.codes)
+        );
+        self.out.write_file(LUA_MODULE, &module)
+    }
+}
+
+/// a single `bundler.lua` module of `{ values = {...}, codes = {...} }`.
+///
+/// runtime files such as `after/` are written as is.
+pub struct LuaModuleBackend;
+
+impl Backend for LuaModuleBackend {
+    fn export(&self, bundle: Bundle, out: &mut dyn Output) -> Result<()> {
+        write_bundle(
+            bundle,
+            &mut LuaModuleSink {
+                out,
+                values: vec![],
+                codes: vec![],
+            },
+        )
+    }
+}
@@ -0,0 +1,79 @@
+use crate::bundle::backend::{write_bundle, Backend, Sink, Value};
+use crate::bundle::flatten::link_runtime;
+use crate::bundle::{Bundle, Output, PluginId, PluginPath};
+use crate::util::vim::to_vim_string;
+use anyhow::Result;
+
+/// value to vim script expression.
+pub fn to_vim_value(value: &Value) -> String {
+    match value {
+        Value::Nil => "v:null".to_string(),
+        Value::Bool(b) => format!("v:{}", b),
+        Value::Number(n) => n.to_string(),
+        Value::Str(s) => to_vim_string(s),
+        Value::List(v) => {
+            let items = v.iter().map(to_vim_value).collect::<Vec<_>>();
+            format!("[{}]", items.join(","))
+        }
+        Value::Dict(v) => {
+            let items = v
+                .iter()
+                .map(|(k, v)| format!("{}:{}", to_vim_string(k), to_vim_value(v)))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", items.join(","))
+        }
+    }
+}
+
+/// one file per key, values as vim script expressions.
+struct VimSink<'o> {
+    out: &'o mut dyn Output,
+}
+
+impl<'o> Sink for VimSink<'o> {
+    fn write_value(&mut self, key: &str, value: Value) -> Result<()> {
+        self.out.write_file(key, &to_vim_value(&value))
+    }
+
+    fn write_code(&mut self, key: &str, code: &str) -> Result<()> {
+        self.out.write_file(key, code)
+    }
+
+    fn write_file(&mut self, path: &str, content: &str) -> Result<()> {
+        self.out.write_file(path, content)
+    }
+
+    fn link_runtime(&mut self, path: &str, plugins: &[(PluginId, PluginPath)]) -> Result<()> {
+        link_runtime(self.out, path, plugins)
+    }
+}
+
+/// the layout for vim, values are read by `eval(join(readfile(path)))` and codes are `:source`d.
+pub struct VimBackend;
+
+impl Backend for VimBackend {
+    fn export(&self, bundle: Bundle, out: &mut dyn Output) -> Result<()> {
+        write_bundle(bundle, &mut VimSink { out })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(arg, exp,
+        case(Value::Nil, "v:null"),
+        case(Value::Bool(true), "v:true"),
+        case(Value::Number(-1), "-1"),
+        case(Value::Str("a\"b\nc".to_string()), r#""a\"b\nc""#),
+        case(Value::strs(&["a", "b"]), r#"["a","b"]"#),
+        case(Value::flags(&["a"]), r#"{"a":v:true}"#),
+        case(Value::Dict(vec![("a".to_string(), Value::strs(&["b"]))]), r#"{"a":["b"]}"#),
+    )]
+    fn test_to_vim_value(arg: Value, exp: &str) {
+        let act = to_vim_value(&arg);
+
+        assert_eq!(exp, act);
+    }
+}
@@ -1,44 +1,190 @@
-use std::collections::HashMap;
+use crate::content::{CommandStub, Language};
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Default, Debug, PartialEq, Eq)]
-pub struct Component<'a> {
-    pub id: &'a str,
+/// serialize a map with sorted keys, so that the document is stable.
+fn sorted<S, K, V>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Ord + Serialize,
+    V: Serialize,
+{
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+/// lazy plugin or group with its configs and dependencies.
+#[derive(Default, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Component {
+    /// plugin id or group name.
+    pub id: String,
+    /// `false` for a group.
     pub is_plugin: bool,
-    pub startup_config: &'a str,
-    pub pre_config: &'a str,
-    pub post_config: &'a str,
-    pub depend_plugins: Vec<&'a str>,
-    pub depend_groups: Vec<&'a str>,
-    pub group_plugins: Vec<&'a str>,
+    /// run at startup, before the component is loaded.
+    pub startup_config: String,
+    /// run before the component is loaded.
+    pub pre_config: String,
+    /// run after the component is loaded.
+    pub post_config: String,
+    /// plugins loaded before the component.
+    pub depend_plugins: Vec<String>,
+    /// groups loaded before the component.
+    pub depend_groups: Vec<String>,
+    /// plugins loaded with the group, empty for a plugin.
+    pub group_plugins: Vec<String>,
+}
+
+/// load triggers of the components, each mapping a trigger to the ids to load.
+#[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadOption {
+    /// store path of every plugin by id.
+    #[serde(serialize_with = "sorted")]
+    pub plugin_paths: HashMap<PluginId, PluginPath>,
+    /// components with a startup config.
+    pub startup_config_plugins: Vec<String>,
+    /// by lua module name.
+    #[serde(serialize_with = "sorted")]
+    pub on_modules: HashMap<String, Vec<String>>,
+    /// by autocmd event.
+    #[serde(serialize_with = "sorted")]
+    pub on_events: HashMap<String, Vec<String>>,
+    /// by filetype.
+    #[serde(serialize_with = "sorted")]
+    pub on_filetypes: HashMap<String, Vec<String>>,
+    /// by command name.
+    #[serde(serialize_with = "sorted")]
+    pub on_commands: HashMap<String, Vec<String>>,
+    /// stubs of `on_commands` by name.
+    pub command_stubs: BTreeMap<String, CommandStub>,
+    /// by function name pattern.
+    #[serde(serialize_with = "sorted")]
+    pub on_functions: HashMap<String, Vec<String>>,
+    /// lazy plugins and groups loaded right after a plugin, by id of the plugin.
+    #[serde(serialize_with = "sorted")]
+    pub followers: HashMap<PluginId, Vec<String>>,
+    /// lazy plugins and groups following an eager plugin, loaded once startup is done.
+    pub startup_followers: Vec<String>,
+    /// by colorscheme name.
+    #[serde(serialize_with = "sorted")]
+    pub on_colorschemes: HashMap<String, Vec<String>>,
+    /// by root marker file.
+    #[serde(serialize_with = "sorted")]
+    pub on_root_markers: HashMap<String, Vec<String>>,
+    /// components loaded by a timer after startup.
+    pub timer_clients: Vec<String>,
+    /// components holding denops plugins.
+    pub denops_clients: Vec<String>,
+    /// denops plugins by id of the plugin shipping them.
+    #[serde(serialize_with = "sorted")]
+    pub denops_scripts: HashMap<PluginId, Vec<DenopsScript>>,
+    /// help tags of all plugins.
+    pub help_tags: Vec<HelpTag>,
+    /// by lua module name.
+    pub lua_modules: BTreeMap<String, LuaModule>,
+    /// ftdetect scripts of lazy plugins, which are sourced before the plugins load.
+    pub ftdetect_scripts: Vec<(PluginId, String)>,
+}
+
+/// lua module resolved at build time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LuaModule {
+    /// `None` when several plugins ship the module, so the runtimepath search picks one.
+    pub file: Option<String>,
+    /// lazy plugins or groups to load before the module is required.
+    pub owners: Vec<String>,
+}
+
+/// help tag of a plugin.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct HelpTag {
+    /// the `*tag*` anchor without the stars.
+    pub tag: String,
+    /// help file defining the tag.
+    pub file: String,
+    /// lazy plugin to load before jumping to the tag, `None` for eager plugins.
+    pub plugin: Option<PluginId>,
+}
+
+/// denops plugin resolved at build time.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct DenopsScript {
+    /// name under `denops/`.
+    pub name: String,
+    /// path of its `main.ts`.
+    pub script: String,
+}
+
+/// code of a file under `after/`.
+#[derive(Serialize)]
+pub struct AfterCode {
+    /// decides the file extension.
+    pub language: Language,
+    /// content of the file.
+    pub code: String,
 }
 
-#[derive(Default)]
-pub struct LoadOption<'a> {
-    pub plugin_paths: HashMap<PluginId<'a>, PluginPath<'a>>,
-    pub startup_config_plugins: Vec<&'a str>,
-    pub on_modules: HashMap<&'a str, Vec<&'a str>>,
-    pub on_events: HashMap<&'a str, Vec<&'a str>>,
-    pub on_filetypes: HashMap<&'a str, Vec<&'a str>>,
-    pub on_commands: HashMap<&'a str, Vec<&'a str>>,
-    pub timer_clients: Vec<&'a str>,
-    pub denops_clients: Vec<&'a str>,
+/// files under `after/` by name without the extension.
+#[derive(Serialize)]
+pub struct AfterOption {
+    /// by filetype, a filetype may have both a vim and a lua file.
+    #[serde(serialize_with = "sorted")]
+    pub ftplugin: HashMap<String, Vec<AfterCode>>,
+    /// by script name.
+    #[serde(serialize_with = "sorted")]
+    pub plugin: HashMap<String, AfterCode>,
+    /// by filetype.
+    #[serde(serialize_with = "sorted")]
+    pub syntax: HashMap<String, AfterCode>,
+    /// by filetype.
+    #[serde(serialize_with = "sorted")]
+    pub indent: HashMap<String, AfterCode>,
+    /// by language server name.
+    #[serde(serialize_with = "sorted")]
+    pub lsp: HashMap<String, AfterCode>,
+    /// treesitter queries by language and query name.
+    #[serde(serialize_with = "sorted")]
+    pub queries: HashMap<String, BTreeMap<String, String>>,
 }
 
-pub struct AfterOption<'a> {
-    pub ftplugin: HashMap<&'a str, &'a str>,
+/// eager plugins merged into a single runtime directory.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EagerRuntime {
+    /// plugins to link, sorted by id.
+    pub plugins: Vec<(PluginId, PluginPath)>,
+    /// help tags of the plugins, as they are found in the merged directory.
+    pub help_tags: Vec<HelpTag>,
 }
 
-pub struct Info<'a> {
-    pub bundler_bin: &'a str,
+/// about the bundler build itself.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Info {
+    /// path of the `bundler` binary.
+    pub bundler_bin: String,
 }
 
-pub type PluginId<'a> = &'a str;
+/// plugin id, e.g. the `pname` of the nix package.
+pub type PluginId = String;
 
-pub type PluginPath<'a> = &'a str;
+/// store path of a plugin.
+pub type PluginPath = String;
 
-pub struct Bundle<'a> {
-    pub components: Vec<Component<'a>>,
-    pub load_option: LoadOption<'a>,
-    pub after_option: AfterOption<'a>,
-    pub info: Info<'a>,
+/// everything exported for the loader, made by [`crate::bundle::bundle`].
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bundle {
+    /// run at startup before the plugins.
+    pub startup_config: String,
+    /// lazy plugins and groups, sorted by id.
+    pub components: Vec<Component>,
+    /// what loads the components.
+    pub load_option: LoadOption,
+    /// files written under `after/`.
+    pub after_option: AfterOption,
+    /// `Some` when eager plugins are flattened.
+    pub eager_runtime: Option<EagerRuntime>,
+    /// about the build.
+    pub info: Info,
 }
@@ -1,208 +1,487 @@
-use crate::bundle::{AfterOption, Component, Info, LoadOption};
-use crate::constant::dir::{AFTER, FTPLUGIN, INFO, MODULES};
+use crate::bundle::backend::{Sink, Value};
+use crate::bundle::{
+    AfterCode, AfterOption, Component, EagerRuntime, HelpTag, Info, LoadOption, PluginId,
+};
+use crate::constant::dir::{
+    AFTER, DOC, EAGER, FTDETECT, FTPLUGIN, INDENT, INFO, LSP, PLUGIN, QUERIES, SYNTAX,
+};
 use crate::constant::file::{
-    BUNDLER_BIN, COMMAND_KEYS, DENOPS_CLIENTS, EVENT_KEYS, FILETYPE_KEYS, MODULE_KEYS,
-    STARTUP_KEYS, TIMER_CLIENTS,
+    BUNDLER_BIN, COLORSCHEME_KEYS, COMMAND_KEYS, COMMAND_STUBS, DENOPS_CLIENTS, EVENT_KEYS,
+    FILETYPE_KEYS, FOLLOWERS, FTDETECT_SCRIPT, FUNCTION_KEYS, HELP_TAGS, LUA_MODULES,
+    LUA_MODULE_OWNERS, ROOT_MARKER_KEYS, STARTUP_FOLLOWERS, STARTUP_KEYS, TAGS, TIMER_CLIENTS,
 };
 use crate::constant::{self, dir};
-use crate::util::file::create_file_with_dirs;
-use crate::util::lua::{to_lua_flag_table, to_lua_table};
+use crate::content::{CommandRange, CommandStub};
+use crate::util::lua::to_lua_string;
+use crate::util::vim::to_vim_string;
 use anyhow::Result;
-use std::io::Write;
+use std::collections::{BTreeMap, HashMap};
+
+/// prefix of the augroup holding the ftdetect autocmds of a lazy plugin.
+const FTDETECT_AUGROUP_PREFIX: &str = "bundler_ftdetect_";
 
-pub struct ExportOption<'a> {
-    pub root_dir: &'a str,
+/// where and how to export a bundle.
+pub struct ExportOption {
+    /// output directory, relative to the current directory or absolute.
+    pub root_dir: String,
+    /// delete files of the previous export that the bundle no longer produces.
+    pub prune: bool,
 }
 
 pub trait Exporter {
-    fn export_file(self, opt: &ExportOption) -> Result<()>;
+    fn export(self, sink: &mut dyn Sink) -> Result<()>;
 }
 
-impl<'a> Exporter for Component<'a> {
-    fn export_file(self, export_option: &ExportOption) -> Result<()> {
+/// help tags in the `:helptags` format.
+fn mk_help_tags(help_tags: &[HelpTag]) -> String {
+    help_tags
+        .iter()
+        .map(|help_tag| {
+            let pattern = help_tag.tag.replace('\\', "\\\\").replace('/', "\\/");
+            format!("{}\t{}\t/*{}*\n", help_tag.tag, help_tag.file, pattern)
+        })
+        .collect()
+}
+
+/// source ftdetect scripts of lazy plugins, each plugin in its own augroup.
+///
+/// `:packadd` defines the autocmds again, so the loader deletes the augroup of the plugin.
+fn mk_ftdetect_code(scripts: &[&(PluginId, String)]) -> String {
+    let mut code = String::new();
+    for (id, script) in scripts {
+        code += &format!(
+            "augroup {}{}\nautocmd!\nexecute 'source ' .. fnameescape({})\n",
+            FTDETECT_AUGROUP_PREFIX,
+            id,
+            to_vim_string(script)
+        );
+    }
+    // this is sourced by `filetype.vim` inside the `filetypedetect` augroup.
+    if !code.is_empty() {
+        code += "augroup filetypedetect\n";
+    }
+    code
+}
+
+/// lua version of `mk_ftdetect_code`.
+///
+/// only autocmds defined in the current augroup are dropped on load, not the ones created
+/// with an explicit `group`.
+fn mk_lua_ftdetect_code(scripts: &[&(PluginId, String)]) -> String {
+    let vim_cmd = |cmd: &str| format!("vim.cmd({})\n", to_lua_string(cmd));
+    let mut code = String::new();
+    for (id, script) in scripts {
+        code += &vim_cmd(&format!("augroup {}{}", FTDETECT_AUGROUP_PREFIX, id));
+        code += &vim_cmd("autocmd!");
+        code += &format!("dofile({})\n", to_lua_string(script));
+    }
+    if !code.is_empty() {
+        code += &vim_cmd("augroup filetypedetect");
+    }
+    code
+}
+
+/// arguments of `nvim_create_user_command` for a stub.
+fn mk_command_stub(stub: &CommandStub) -> Value {
+    // `0` and `1` are only accepted as numbers.
+    let nargs = match stub.nargs.parse::<i64>() {
+        Ok(n) => Value::Number(n),
+        Err(_) => Value::Str(stub.nargs.clone()),
+    };
+    let range = match stub.range {
+        CommandRange::None => Value::Bool(false),
+        CommandRange::Line => Value::Bool(true),
+        CommandRange::File => Value::Str("%".to_string()),
+        CommandRange::Count(n) => Value::Number(n.into()),
+    };
+    let complete = if stub.complete.is_empty() {
+        Value::Nil
+    } else {
+        Value::Str(stub.complete.clone())
+    };
+    Value::Dict(vec![
+        ("nargs".to_string(), nargs),
+        ("range".to_string(), range),
+        ("bang".to_string(), Value::Bool(stub.bang)),
+        ("complete".to_string(), complete),
+    ])
+}
+
+/// write `<keys>` and `<dir>/<key>` of a trigger index.
+fn export_index(
+    sink: &mut dyn Sink,
+    keys_file: &str,
+    dir: &str,
+    index: HashMap<String, Vec<String>>,
+) -> Result<()> {
+    let mut keys = index.keys().cloned().collect::<Vec<_>>();
+    keys.sort();
+    sink.write_value(keys_file, Value::strs(&keys))?;
+    for (key, plugins) in index.into_iter().collect::<BTreeMap<_, _>>() {
+        sink.write_value(&format!("{}/{}", dir, key), Value::strs(&plugins))?;
+    }
+
+    Ok(())
+}
+
+impl Exporter for Component {
+    fn export(self, sink: &mut dyn Sink) -> Result<()> {
         // plugin
-        let mut plugin_file = create_file_with_dirs(
-            String::from(export_option.root_dir) + "/" + dir::PLUGIN + "/" + self.id,
-        )?;
-        if self.is_plugin {
-            write!(plugin_file, "return \"{}\"", self.id)?;
+        let plugin = if self.is_plugin {
+            Value::Str(self.id.to_string())
         } else {
-            write!(plugin_file, "return nil")?;
-        }
+            Value::Nil
+        };
+        sink.write_value(&format!("{}/{}", dir::PLUGIN, self.id), plugin)?;
 
         // plugins
-        let mut plugins_file = create_file_with_dirs(
-            String::from(export_option.root_dir) + "/" + dir::PLUGINS + "/" + self.id,
+        sink.write_value(
+            &format!("{}/{}", dir::PLUGINS, self.id),
+            Value::strs(&self.group_plugins),
         )?;
-        write!(plugins_file, "return {}", to_lua_table(&self.group_plugins))?;
 
         // startup
-        let mut startup_file = create_file_with_dirs(
-            String::from(export_option.root_dir) + "/" + dir::STARTUP + "/" + self.id,
+        sink.write_code(
+            &format!("{}/{}", dir::STARTUP, self.id),
+            &self.startup_config,
         )?;
-        write!(startup_file, "{}", self.startup_config)?;
 
         // pre_config
-        let mut pre_config_file = create_file_with_dirs(
-            String::from(export_option.root_dir) + "/" + dir::PRE_CONFIG + "/" + self.id,
+        sink.write_code(
+            &format!("{}/{}", dir::PRE_CONFIG, self.id),
+            &self.pre_config,
         )?;
-        write!(pre_config_file, "{}", self.pre_config)?;
 
         // post_config
-        let mut post_config_file = create_file_with_dirs(
-            String::from(export_option.root_dir) + "/" + dir::POST_CONFIG + "/" + self.id,
+        sink.write_code(
+            &format!("{}/{}", dir::POST_CONFIG, self.id),
+            &self.post_config,
         )?;
-        write!(post_config_file, "{}", self.post_config)?;
 
         // depend plugins
-        let mut depend_plugins_file = create_file_with_dirs(
-            String::from(export_option.root_dir) + "/" + dir::DEPEND_PLUGINS + "/" + self.id,
-        )?;
-        write!(
-            depend_plugins_file,
-            "return {}",
-            to_lua_table(&self.depend_plugins)
+        sink.write_value(
+            &format!("{}/{}", dir::DEPEND_PLUGINS, self.id),
+            Value::strs(&self.depend_plugins),
         )?;
 
         // depend groups
-        let mut depend_groups_file = create_file_with_dirs(
-            String::from(export_option.root_dir) + "/" + dir::DEPEND_GROUPS + "/" + self.id,
-        )?;
-        write!(
-            depend_groups_file,
-            "return {}",
-            to_lua_table(&self.depend_groups)
+        sink.write_value(
+            &format!("{}/{}", dir::DEPEND_GROUPS, self.id),
+            Value::strs(&self.depend_groups),
         )?;
 
         Ok(())
     }
 }
 
-impl<'a> Exporter for LoadOption<'a> {
-    fn export_file(self, export_option: &ExportOption) -> Result<()> {
+impl Exporter for LoadOption {
+    fn export(self, sink: &mut dyn Sink) -> Result<()> {
         // plugin paths
-        for (plugin_id, path) in self.plugin_paths {
-            let mut plugin_path_file = create_file_with_dirs(
-                String::from(export_option.root_dir) + "/" + constant::dir::RTP + "/" + plugin_id,
+        for (plugin_id, path) in self.plugin_paths.into_iter().collect::<BTreeMap<_, _>>() {
+            sink.write_value(
+                &format!("{}/{}", constant::dir::RTP, plugin_id),
+                Value::Str(path.to_string()),
             )?;
-            write!(plugin_path_file, "return \"{}\"", path)?;
         }
 
         // startup plugins
-        let mut startup_plugins_file =
-            create_file_with_dirs(String::from(export_option.root_dir) + "/" + STARTUP_KEYS)?;
-        write!(
-            startup_plugins_file,
-            "return {}",
-            to_lua_table(&self.startup_config_plugins)
-        )?;
+        sink.write_value(STARTUP_KEYS, Value::strs(&self.startup_config_plugins))?;
 
-        // modules
-        let mut modules_file =
-            create_file_with_dirs(String::from(export_option.root_dir) + "/" + MODULE_KEYS)?;
-        let modules = self.on_modules.keys().cloned().collect::<Vec<_>>();
-        write!(modules_file, "return {}", to_lua_table(&modules))?;
-        for (module, plugins) in self.on_modules {
-            let mut file = create_file_with_dirs(
-                String::from(export_option.root_dir) + "/" + MODULES + "/" + module,
-            )?;
-            write!(file, "return {}", to_lua_table(&plugins))?;
+        // lua modules
+        let lua_modules = self
+            .lua_modules
+            .iter()
+            .filter_map(|(name, module)| Some((name.as_str(), module.file.as_deref()?)))
+            .collect::<Vec<_>>();
+        sink.write_value(LUA_MODULES, Value::str_dict(&lua_modules))?;
+
+        // plugins to load before requiring the module. configured `on_modules` take precedence.
+        let mut owners = self
+            .lua_modules
+            .iter()
+            .filter(|(_, module)| !module.owners.is_empty())
+            .map(|(name, module)| (name.as_str(), module.owners.clone()))
+            .collect::<BTreeMap<_, _>>();
+        for (module, plugins) in &self.on_modules {
+            owners.insert(module, plugins.clone());
         }
+        let owners = owners
+            .into_iter()
+            .map(|(name, plugins)| (name.to_string(), Value::strs(&plugins)))
+            .collect::<Vec<_>>();
+        sink.write_value(LUA_MODULE_OWNERS, Value::Dict(owners))?;
 
         // events
-        let mut events_file =
-            create_file_with_dirs(String::from(export_option.root_dir) + "/" + EVENT_KEYS)?;
-        let events = self.on_events.keys().cloned().collect::<Vec<_>>();
-        write!(events_file, "return {}", to_lua_table(&events))?;
-        for (event, plugins) in self.on_events {
-            let mut file = create_file_with_dirs(
-                String::from(export_option.root_dir) + "/" + constant::dir::EVENTS + "/" + event,
-            )?;
-            write!(file, "return {}", to_lua_table(&plugins))?;
-        }
+        export_index(sink, EVENT_KEYS, constant::dir::EVENTS, self.on_events)?;
 
         // filetypes
-        let mut filetypes_file =
-            create_file_with_dirs(String::from(export_option.root_dir) + "/" + FILETYPE_KEYS)?;
-        let filetypes = self.on_filetypes.keys().cloned().collect::<Vec<_>>();
-        write!(filetypes_file, "return {}", to_lua_table(&filetypes))?;
-        for (filetype, plugins) in self.on_filetypes {
-            let mut file = create_file_with_dirs(
-                String::from(export_option.root_dir)
-                    + "/"
-                    + constant::dir::FILETYPES
-                    + "/"
-                    + filetype,
-            )?;
-            write!(file, "return {}", to_lua_table(&plugins))?;
-        }
+        export_index(
+            sink,
+            FILETYPE_KEYS,
+            constant::dir::FILETYPES,
+            self.on_filetypes,
+        )?;
 
         // commands
-        let mut commands_file =
-            create_file_with_dirs(String::from(export_option.root_dir) + "/" + COMMAND_KEYS)?;
-        let commands = self.on_commands.keys().cloned().collect::<Vec<_>>();
-        write!(commands_file, "return {}", to_lua_table(&commands))?;
-        for (command, plugins) in self.on_commands {
-            let mut file = create_file_with_dirs(
-                String::from(export_option.root_dir)
-                    + "/"
-                    + constant::dir::COMMANDS
-                    + "/"
-                    + command,
-            )?;
-            write!(file, "return {}", to_lua_table(&plugins))?;
-        }
+        export_index(
+            sink,
+            COMMAND_KEYS,
+            constant::dir::COMMANDS,
+            self.on_commands,
+        )?;
 
-        // timer clients
-        let mut timer_clients_file =
-            create_file_with_dirs(String::from(export_option.root_dir) + "/" + TIMER_CLIENTS)?;
-        write!(
-            timer_clients_file,
-            "return {}",
-            to_lua_table(&self.timer_clients)
+        // command stubs
+        let command_stubs = self
+            .command_stubs
+            .iter()
+            .map(|(name, stub)| (name.to_string(), mk_command_stub(stub)))
+            .collect::<Vec<_>>();
+        sink.write_value(COMMAND_STUBS, Value::Dict(command_stubs))?;
+
+        // functions
+        export_index(
+            sink,
+            FUNCTION_KEYS,
+            constant::dir::FUNCTIONS,
+            self.on_functions,
+        )?;
+
+        // followers
+        let followers = self
+            .followers
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .map(|(id, plugins)| (id.to_string(), Value::strs(&plugins)))
+            .collect::<Vec<_>>();
+        sink.write_value(FOLLOWERS, Value::Dict(followers))?;
+        sink.write_value(STARTUP_FOLLOWERS, Value::strs(&self.startup_followers))?;
+
+        // colorschemes
+        export_index(
+            sink,
+            COLORSCHEME_KEYS,
+            constant::dir::COLORSCHEMES,
+            self.on_colorschemes,
         )?;
 
+        // root markers
+        export_index(
+            sink,
+            ROOT_MARKER_KEYS,
+            constant::dir::ROOT_MARKERS,
+            self.on_root_markers,
+        )?;
+
+        // timer clients
+        sink.write_value(TIMER_CLIENTS, Value::strs(&self.timer_clients))?;
+
         // denops clients
-        let mut denops_clients_file =
-            create_file_with_dirs(String::from(export_option.root_dir) + "/" + DENOPS_CLIENTS)?;
-        write!(
-            denops_clients_file,
-            "return {}",
-            to_lua_flag_table(&self.denops_clients, true)
+        sink.write_value(DENOPS_CLIENTS, Value::flags(&self.denops_clients))?;
+        for (id, scripts) in self.denops_scripts.into_iter().collect::<BTreeMap<_, _>>() {
+            let scripts = scripts
+                .iter()
+                .map(|s| {
+                    Value::str_dict(&[("name", s.name.as_str()), ("script", s.script.as_str())])
+                })
+                .collect::<Vec<_>>();
+            sink.write_value(
+                &format!("{}/{}", constant::dir::DENOPS, id),
+                Value::List(scripts),
+            )?;
+        }
+
+        // ftdetect (`after` is on runtimepath, so `:filetype on` sources them)
+        let (lua_scripts, vim_scripts): (Vec<_>, Vec<_>) = self
+            .ftdetect_scripts
+            .iter()
+            .partition(|(_, script)| script.ends_with(".lua"));
+        if !vim_scripts.is_empty() {
+            sink.write_file(
+                &format!("{}/{}/{}.vim", AFTER, FTDETECT, FTDETECT_SCRIPT),
+                &mk_ftdetect_code(&vim_scripts),
+            )?;
+        }
+        if !lua_scripts.is_empty() {
+            sink.write_file(
+                &format!("{}/{}/{}.lua", AFTER, FTDETECT, FTDETECT_SCRIPT),
+                &mk_lua_ftdetect_code(&lua_scripts),
+            )?;
+        }
+
+        // help tags (`after` is on runtimepath, so `:help` finds them)
+        sink.write_file(
+            &format!("{}/{}/{}", AFTER, DOC, TAGS),
+            &mk_help_tags(&self.help_tags),
         )?;
+        let help_tags = self
+            .help_tags
+            .iter()
+            .filter_map(|help_tag| Some((help_tag.tag.as_str(), help_tag.plugin.as_deref()?)))
+            .collect::<Vec<_>>();
+        sink.write_value(HELP_TAGS, Value::str_dict(&help_tags))?;
 
         Ok(())
     }
 }
 
-impl<'a> Exporter for AfterOption<'a> {
-    fn export_file(self, export_option: &ExportOption) -> Result<()> {
-        // filetypes
-        for (filetype, code) in self.ftplugin {
-            let mut file = create_file_with_dirs(
-                String::from(export_option.root_dir)
-                    + "/"
-                    + AFTER
-                    + "/"
-                    + FTPLUGIN
-                    + "/"
-                    + filetype
-                    + ".vim",
-            )?;
-            write!(file, "{}", code)?;
+/// write `after/<dir>/<name>.<vim|lua>`.
+fn export_after_code(sink: &mut dyn Sink, dir: &str, name: &str, code: AfterCode) -> Result<()> {
+    sink.write_file(
+        &format!("{}/{}/{}.{}", AFTER, dir, name, code.language),
+        &code.code,
+    )
+}
+
+fn export_after_codes(
+    sink: &mut dyn Sink,
+    dir: &str,
+    codes: HashMap<String, AfterCode>,
+) -> Result<()> {
+    for (name, code) in codes.into_iter().collect::<BTreeMap<_, _>>() {
+        export_after_code(sink, dir, &name, code)?;
+    }
+
+    Ok(())
+}
+
+impl Exporter for AfterOption {
+    fn export(self, sink: &mut dyn Sink) -> Result<()> {
+        // both `<ft>.vim` and `<ft>.lua` are sourced when a filetype has both.
+        for (filetype, codes) in self.ftplugin.into_iter().collect::<BTreeMap<_, _>>() {
+            for code in codes {
+                export_after_code(sink, FTPLUGIN, &filetype, code)?;
+            }
+        }
+        export_after_codes(sink, PLUGIN, self.plugin)?;
+        export_after_codes(sink, SYNTAX, self.syntax)?;
+        export_after_codes(sink, INDENT, self.indent)?;
+        export_after_codes(sink, LSP, self.lsp)?;
+
+        // queries
+        for (language, queries) in self.queries.into_iter().collect::<BTreeMap<_, _>>() {
+            for (name, query) in queries {
+                sink.write_file(
+                    &format!("{}/{}/{}/{}.scm", AFTER, QUERIES, language, name),
+                    &query,
+                )?;
+            }
         }
 
         Ok(())
     }
 }
 
-impl<'a> Exporter for Info<'a> {
-    fn export_file(self, opt: &ExportOption) -> Result<()> {
+impl Exporter for EagerRuntime {
+    fn export(self, sink: &mut dyn Sink) -> Result<()> {
+        sink.link_runtime(EAGER, &self.plugins)?;
+
+        // help tags
+        sink.write_file(
+            &format!("{}/{}/{}", EAGER, DOC, TAGS),
+            &mk_help_tags(&self.help_tags),
+        )?;
+
+        Ok(())
+    }
+}
+
+impl Exporter for Info {
+    fn export(self, sink: &mut dyn Sink) -> Result<()> {
         // bundler bin
-        let mut bundler_bin_file =
-            create_file_with_dirs(String::from(opt.root_dir) + "/" + INFO + "/" + BUNDLER_BIN)?;
-        write!(bundler_bin_file, "return \"{}\"", self.bundler_bin)?;
+        sink.write_value(
+            &format!("{}/{}", INFO, BUNDLER_BIN),
+            Value::Str(self.bundler_bin.to_string()),
+        )?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_mk_ftdetect_code() {
+        let scripts = [
+            (
+                "foo".to_string(),
+                "/nix/store/foo/ftdetect/foo.vim".to_string(),
+            ),
+            (
+                "bar".to_string(),
+                "/tmp/it's a \"bar\"/ftdetect/bar.vim".to_string(),
+            ),
+        ];
+
+        let act = mk_ftdetect_code(&scripts.iter().collect::<Vec<_>>());
+
+        let exp = [
+            "augroup bundler_ftdetect_foo",
+            "autocmd!",
+            r#"execute 'source ' .. fnameescape("/nix/store/foo/ftdetect/foo.vim")"#,
+            "augroup bundler_ftdetect_bar",
+            "autocmd!",
+            r#"execute 'source ' .. fnameescape("/tmp/it's a \"bar\"/ftdetect/bar.vim")"#,
+            "augroup filetypedetect",
+            "",
+        ];
+        assert_eq!(exp.join("\n"), act);
+        assert_eq!("", mk_ftdetect_code(&[]));
+    }
+
+    #[test]
+    fn test_mk_lua_ftdetect_code() {
+        let scripts = [(
+            "foo".to_string(),
+            "/nix/store/foo/ftdetect/foo.lua".to_string(),
+        )];
+
+        let act = mk_lua_ftdetect_code(&scripts.iter().collect::<Vec<_>>());
+
+        let exp = [
+            r#"vim.cmd("augroup bundler_ftdetect_foo")"#,
+            r#"vim.cmd("autocmd!")"#,
+            r#"dofile("/nix/store/foo/ftdetect/foo.lua")"#,
+            r#"vim.cmd("augroup filetypedetect")"#,
+            "",
+        ];
+        assert_eq!(exp.join("\n"), act);
+        assert_eq!("", mk_lua_ftdetect_code(&[]));
+    }
+
+    #[rstest(nargs, range, complete, exp_nargs, exp_range, exp_complete,
+        case("0", CommandRange::None, "", Value::Number(0), Value::Bool(false), Value::Nil),
+        case("1", CommandRange::Line, "file", Value::Number(1), Value::Bool(true), Value::Str("file".to_string())),
+        case("*", CommandRange::File, "", Value::Str("*".to_string()), Value::Str("%".to_string()), Value::Nil),
+        case("?", CommandRange::Count(3), "", Value::Str("?".to_string()), Value::Number(3), Value::Nil),
+    )]
+    fn test_mk_command_stub(
+        nargs: &str,
+        range: CommandRange,
+        complete: &str,
+        exp_nargs: Value,
+        exp_range: Value,
+        exp_complete: Value,
+    ) {
+        let stub = CommandStub {
+            name: "Foo".to_string(),
+            nargs: nargs.to_string(),
+            range,
+            bang: true,
+            complete: complete.to_string(),
+        };
+
+        let act = mk_command_stub(&stub);
+
+        let exp = Value::Dict(vec![
+            ("nargs".to_string(), exp_nargs),
+            ("range".to_string(), exp_range),
+            ("bang".to_string(), Value::Bool(true)),
+            ("complete".to_string(), exp_complete),
+        ]);
+        assert_eq!(exp, act);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::bundle::{Output, PluginId, PluginPath};
+use crate::util::file::read_dir_entries;
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// plugin directories that are looked up through runtimepath.
+const RUNTIME_DIRS: [&str; 20] = [
+    "after", "autoload", "colors", "compiler", "denops", "doc", "ftdetect", "ftplugin", "indent",
+    "keymap", "lang", "lua", "pack", "parser", "plugin", "queries", "rplugin", "spell", "syntax",
+    "tutor",
+];
+
+/// `doc/tags` of each plugin collide, so it is regenerated for the merged tree.
+const HELP_TAGS: &str = "doc/tags";
+
+fn is_same_file(a: &Path, b: &Path) -> Result<bool> {
+    Ok(a.is_file() && b.is_file() && fs::read(a)? == fs::read(b)?)
+}
+
+/// merge `sources` that share the same runtime-relative path into `dest`.
+///
+/// like nix `buildEnv`, a path owned by one plugin is symlinked as is,
+/// directories owned by several plugins are merged recursively,
+/// and files owned by several plugins are collisions unless identical.
+fn merge_tree(
+    out: &mut dyn Output,
+    dest: &str,
+    relative: &str,
+    sources: Vec<(&str, PathBuf)>,
+) -> Result<()> {
+    if relative == HELP_TAGS {
+        return Ok(());
+    }
+
+    let (_, first) = &sources[0];
+    if sources.len() == 1 && relative != "doc" {
+        return out.symlink(dest, first);
+    }
+
+    if sources.iter().all(|(_, path)| path.is_dir()) {
+        out.create_dir(dest)?;
+        let mut children: BTreeMap<String, Vec<(&str, PathBuf)>> = BTreeMap::new();
+        for (id, dir) in &sources {
+            for (name, path) in read_dir_entries(dir)? {
+                children.entry(name).or_default().push((id, path));
+            }
+        }
+        for (name, sources) in children {
+            merge_tree(
+                out,
+                &format!("{}/{}", dest, name),
+                &format!("{}/{}", relative, name),
+                sources,
+            )?;
+        }
+        return Ok(());
+    }
+
+    for (id, path) in &sources[1..] {
+        if !is_same_file(first, path)? {
+            bail!(
+                "Collision between `{}` ({}) and `{}` ({}).",
+                first.display(),
+                sources[0].0,
+                path.display(),
+                id
+            )
+        }
+    }
+    out.symlink(dest, first)
+}
+
+/// build a single runtime directory of symlinks from the plugins.
+pub fn link_runtime(
+    out: &mut dyn Output,
+    dest: &str,
+    plugins: &[(PluginId, PluginPath)],
+) -> Result<()> {
+    out.create_dir(dest)?;
+
+    let mut dirs: BTreeMap<&str, Vec<(&str, PathBuf)>> = BTreeMap::new();
+    for (id, plugin_path) in plugins {
+        for dir in RUNTIME_DIRS {
+            let path = Path::new(plugin_path).join(dir);
+            if path.is_dir() {
+                dirs.entry(dir).or_default().push((id.as_str(), path));
+            }
+        }
+    }
+    for (dir, sources) in dirs {
+        merge_tree(out, &format!("{}/{}", dest, dir), dir, sources)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::DirOutput;
+    use crate::util::temp::TempDir;
+
+    #[test]
+    fn test_link_runtime() {
+        let root = TempDir::new("flatten");
+        for (file, content) in [
+            ("foo/lua/foo/init.lua", "foo"),
+            ("foo/plugin/foo.vim", "foo"),
+            ("foo/doc/tags", "foo"),
+            ("foo/README.md", "foo"),
+            ("bar/lua/bar.lua", "bar"),
+            ("bar/plugin/common.vim", "same"),
+            ("bar/doc/tags", "bar"),
+            ("baz/plugin/common.vim", "same"),
+        ] {
+            root.write(file, content);
+        }
+        let paths = ["foo", "bar", "baz"].map(|p| root.join(p).to_string_lossy().to_string());
+        let plugins = ["foo", "bar", "baz"]
+            .into_iter()
+            .map(str::to_string)
+            .zip(paths)
+            .collect::<Vec<_>>();
+        let dest = root.join("out");
+        let mut out = DirOutput {
+            root_dir: root.path().to_path_buf(),
+        };
+
+        link_runtime(&mut out, "out", &plugins).unwrap();
+
+        assert!(dest.join("lua/foo").is_symlink());
+        assert!(dest.join("lua/bar.lua").is_symlink());
+        assert!(dest.join("plugin/common.vim").is_symlink());
+        assert!(!dest.join("doc").is_symlink());
+        assert!(!dest.join("doc/tags").exists());
+        assert!(!dest.join("README.md").exists());
+
+        root.write("baz/plugin/foo.vim", "baz");
+        let act = link_runtime(&mut out, "out2", &plugins);
+
+        assert!(act.is_err());
+    }
+}
@@ -29,15 +29,15 @@ where
     }
 }
 
-impl<'a> Mergeable for config::Component<'a> {
+impl Mergeable for config::Component {
     fn id(&self) -> &str {
-        self.id
+        &self.id
     }
 
     fn modified(&self) -> bool {
         let base = config::Component {
-            id: self.id,
-            is_plugin: self.is_plugin.clone(),
+            id: self.id.clone(),
+            is_plugin: self.is_plugin,
             // compare other fields
             ..Default::default()
         };
@@ -0,0 +1,141 @@
+use crate::util::file::create_file_with_dirs;
+use crate::util::tar::TarWriter;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+/// where the exported files go, paths are relative to the export root.
+pub trait Output {
+    /// a file at `path`.
+    fn write_file(&mut self, path: &str, content: &str) -> Result<()>;
+
+    /// a directory at `path`.
+    fn create_dir(&mut self, path: &str) -> Result<()>;
+
+    /// a symlink at `path` pointing to `target`.
+    fn symlink(&mut self, path: &str, target: &Path) -> Result<()>;
+}
+
+/// files written under `root_dir`.
+pub struct DirOutput {
+    pub root_dir: PathBuf,
+}
+
+impl Output for DirOutput {
+    fn write_file(&mut self, path: &str, content: &str) -> Result<()> {
+        let mut file = create_file_with_dirs(self.root_dir.join(path))?;
+        write!(file, "{}", content)?;
+
+        Ok(())
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<()> {
+        fs::create_dir_all(self.root_dir.join(path))?;
+
+        Ok(())
+    }
+
+    fn symlink(&mut self, path: &str, target: &Path) -> Result<()> {
+        let path = self.root_dir.join(path);
+        if let Some(parent_dir) = path.parent() {
+            fs::create_dir_all(parent_dir)?;
+        }
+        symlink(target, &path).with_context(|| format!("failed to link `{}`", path.display()))
+    }
+}
+
+/// entries streamed into a tar archive as they are written.
+pub struct TarOutput<W: Write> {
+    tar: TarWriter<W>,
+    dirs: HashSet<String>,
+}
+
+impl<W: Write> TarOutput<W> {
+    pub fn new(writer: W) -> Self {
+        TarOutput {
+            tar: TarWriter::new(writer),
+            dirs: HashSet::new(),
+        }
+    }
+
+    /// append `dir` and its parents that are not in the archive yet.
+    fn append_dirs(&mut self, dir: &str) -> Result<()> {
+        if dir.is_empty() || self.dirs.contains(dir) {
+            return Ok(());
+        }
+        if let Some((parent_dir, _)) = dir.rsplit_once('/') {
+            self.append_dirs(parent_dir)?;
+        }
+        self.tar.append_dir(dir)?;
+        self.dirs.insert(dir.to_string());
+
+        Ok(())
+    }
+
+    fn append_parent_dirs(&mut self, path: &str) -> Result<()> {
+        match path.rsplit_once('/') {
+            Some((parent_dir, _)) => self.append_dirs(parent_dir),
+            None => Ok(()),
+        }
+    }
+
+    /// write the end-of-archive marker.
+    pub fn finish(self) -> Result<W> {
+        self.tar.finish()
+    }
+}
+
+impl<W: Write> Output for TarOutput<W> {
+    fn write_file(&mut self, path: &str, content: &str) -> Result<()> {
+        self.append_parent_dirs(path)?;
+        self.tar.append_file(path, content.as_bytes())
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<()> {
+        self.append_dirs(path)
+    }
+
+    fn symlink(&mut self, path: &str, target: &Path) -> Result<()> {
+        self.append_parent_dirs(path)?;
+        self.tar.append_symlink(path, &target.to_string_lossy())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tar_output() {
+        let mut out = TarOutput::new(vec![]);
+        out.write_file("after/doc/tags", "tags").unwrap();
+        out.symlink("after/lua/foo", Path::new("/nix/store/foo"))
+            .unwrap();
+        out.create_dir("after/lua").unwrap();
+        let act = out.finish().unwrap();
+
+        // `after/`, `after/doc/`, the file with its data, `after/lua/` and the symlink.
+        let names = act
+            .chunks(512)
+            .filter(|block| block[257..263] == *b"ustar\0")
+            .map(|block| {
+                let name = &block[..100];
+                let len = name.iter().position(|b| *b == 0).unwrap_or(100);
+                String::from_utf8_lossy(&name[..len]).to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "after/",
+                "after/doc/",
+                "after/doc/tags",
+                "after/lua/",
+                "after/lua/foo"
+            ],
+            names
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use crate::bundle::{DenopsScript, PluginId, PluginPath};
+use crate::util::file::{read_dir_entries, read_files_recursive};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// runtime directories where files of different plugins shadow each other.
+///
+/// `plugin/` and `ftplugin/` scripts of the same name are all sourced, but they usually
+/// share a `g:loaded_*` or `b:did_ftplugin` guard, so the first one on the runtimepath wins.
+const SHADOWED_DIRS: [&str; 5] = ["lua", "autoload", "plugin", "ftplugin", "colors"];
+
+/// find `denops/<name>/main.ts` in the plugin.
+pub fn find_denops_scripts(plugin_path: &str) -> Result<Vec<DenopsScript>> {
+    let denops_dir = Path::new(plugin_path).join("denops");
+    if !denops_dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut scripts = vec![];
+    for entry in fs::read_dir(&denops_dir)
+        .with_context(|| format!("failed to read `{}`", denops_dir.display()))?
+    {
+        let path = entry?.path();
+        let script = path.join("main.ts");
+        if !script.is_file() {
+            continue;
+        }
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            scripts.push(DenopsScript {
+                name: name.to_string(),
+                script: script.to_string_lossy().to_string(),
+            });
+        }
+    }
+    scripts.sort();
+
+    Ok(scripts)
+}
+
+/// find runtime-relative files shipped by more than one plugin.
+///
+/// returns `path -> plugin ids` sorted by path.
+pub fn find_runtime_collisions(
+    plugin_paths: &HashMap<PluginId, PluginPath>,
+) -> Result<BTreeMap<String, Vec<&str>>> {
+    let mut owners: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for (id, plugin_path) in plugin_paths {
+        let root = Path::new(plugin_path);
+        for dir in SHADOWED_DIRS {
+            for file in read_files_recursive(&root.join(dir))? {
+                let relative = file.strip_prefix(root)?.to_string_lossy().to_string();
+                owners.entry(relative).or_default().push(id.as_str());
+            }
+        }
+    }
+    owners.retain(|_, ids| ids.len() > 1);
+    for ids in owners.values_mut() {
+        ids.sort();
+    }
+
+    Ok(owners)
+}
+
+/// module name of `lua/<relative>`, e.g. `foo/bar/init.lua` -> `foo.bar`.
+///
+/// the flag tells whether the file is an `init.lua` of the module.
+fn lua_module_name(relative: &str) -> Option<(String, bool)> {
+    let stem = relative.strip_suffix(".lua")?;
+    match stem.strip_suffix("/init") {
+        Some(parent) => Some((parent.replace('/', "."), true)),
+        None if stem.is_empty() => None,
+        None => Some((stem.replace('/', "."), false)),
+    }
+}
+
+/// lua modules of the plugin as `(module, file)`.
+///
+/// `lua/<mod>.lua` takes precedence over `lua/<mod>/init.lua` as `require` does.
+pub fn find_lua_modules(plugin_path: &str) -> Result<Vec<(String, String)>> {
+    let lua_dir = Path::new(plugin_path).join("lua");
+    let mut modules: BTreeMap<String, (bool, String)> = BTreeMap::new();
+    for file in read_files_recursive(&lua_dir)? {
+        let relative = file.strip_prefix(&lua_dir)?.to_string_lossy().to_string();
+        if let Some((name, is_init)) = lua_module_name(&relative) {
+            let file = file.to_string_lossy().to_string();
+            match modules.get(&name) {
+                Some((false, _)) => {}
+                Some(_) if is_init => {}
+                _ => {
+                    modules.insert(name, (is_init, file));
+                }
+            }
+        }
+    }
+
+    Ok(modules
+        .into_iter()
+        .map(|(name, (_, file))| (name, file))
+        .collect())
+}
+
+/// `ftdetect/*.vim` and `ftdetect/*.lua` of the plugin.
+pub fn find_ftdetect_scripts(plugin_path: &str) -> Result<Vec<String>> {
+    let mut scripts = vec![];
+    for (name, path) in read_dir_entries(&Path::new(plugin_path).join("ftdetect"))? {
+        if (name.ends_with(".vim") || name.ends_with(".lua")) && path.is_file() {
+            scripts.push(path.to_string_lossy().to_string());
+        }
+    }
+    Ok(scripts)
+}
+
+/// `*tag*` anchors in a help file, as `:helptags` recognizes them.
+fn parse_help_tags(text: &str) -> Vec<String> {
+    let mut tags = vec![];
+    for line in text.lines() {
+        let chars = line.char_indices().collect::<Vec<_>>();
+        let mut i = 0;
+        while i < chars.len() {
+            let (start, c) = chars[i];
+            let preceded = i == 0 || chars[i - 1].1.is_whitespace();
+            if c != '*' || !preceded {
+                i += 1;
+                continue;
+            }
+            let end = chars[i + 1..]
+                .iter()
+                .position(|(_, c)| *c == '*' || *c == '|' || c.is_whitespace())
+                .map(|n| i + 1 + n);
+            match end {
+                Some(j) if j > i + 1 && chars[j].1 == '*' => {
+                    let followed = chars.get(j + 1).map_or(true, |(_, c)| c.is_whitespace());
+                    if followed {
+                        tags.push(line[start + 1..chars[j].0].to_string());
+                    }
+                    i = j + 1;
+                }
+                _ => i += 1,
+            }
+        }
+    }
+    tags
+}
+
+/// help tags in `doc/*.txt` of the plugin as `(tag, file)`.
+pub fn find_help_tags(plugin_path: &str) -> Result<Vec<(String, String)>> {
+    let mut tags = vec![];
+    for (name, path) in read_dir_entries(&Path::new(plugin_path).join("doc"))? {
+        if !name.ends_with(".txt") || !path.is_file() {
+            continue;
+        }
+        let text = String::from_utf8_lossy(&fs::read(&path)?).to_string();
+        let file = path.to_string_lossy().to_string();
+        for tag in parse_help_tags(&text) {
+            tags.push((tag, file.clone()));
+        }
+    }
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp::TempDir;
+    use rstest::rstest;
+
+    #[rstest(arg, exp,
+        case("foo.lua", Some(("foo", false))),
+        case("foo/init.lua", Some(("foo", true))),
+        case("foo/bar.lua", Some(("foo.bar", false))),
+        case("foo/bar/init.lua", Some(("foo.bar", true))),
+        case("foo/bar.vim", None),
+    )]
+    fn test_lua_module_name(arg: &str, exp: Option<(&str, bool)>) {
+        let act = lua_module_name(arg);
+
+        assert_eq!(exp.map(|(name, is_init)| (name.to_string(), is_init)), act);
+    }
+
+    #[rstest(arg, exp,
+        case("", vec![]),
+        case("*foo*", vec!["foo"]),
+        case("*foo.txt*  For Vim version 9.0   *foo* *:Foo*", vec!["foo.txt", "foo", ":Foo"]),
+        case("see |foo| and a*b*c or *not closed", vec![]),
+        case("*a* *b*\n\t*c()*", vec!["a", "b", "c()"]),
+        case("** *x y*", vec![]),
+    )]
+    fn test_parse_help_tags(arg: &str, exp: Vec<&str>) {
+        let act = parse_help_tags(arg);
+
+        assert_eq!(exp, act);
+    }
+
+    #[test]
+    fn test_find_denops_scripts() {
+        let root = TempDir::new("denops");
+        root.write("denops/foo/main.ts", "");
+        root.write("denops/@bar/main.ts", "");
+        root.write("denops/baz/util.ts", "");
+
+        let act = find_denops_scripts(root.path().to_str().unwrap()).unwrap();
+
+        let names = act.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["@bar", "foo"], names);
+        assert!(act[1].script.ends_with("denops/foo/main.ts"));
+    }
+
+    #[test]
+    fn test_find_runtime_collisions() {
+        let root = TempDir::new("collisions");
+        for file in [
+            "foo/lua/utils/init.lua",
+            "foo/autoload/foo.vim",
+            "foo/doc/README.md",
+            "bar/lua/utils/init.lua",
+            "bar/autoload/bar.vim",
+            "bar/doc/README.md",
+            "foo/plugin/common.vim",
+            "bar/plugin/common.vim",
+        ] {
+            root.write(file, "");
+        }
+        let foo = root.join("foo").to_string_lossy().to_string();
+        let bar = root.join("bar").to_string_lossy().to_string();
+        let plugin_paths = HashMap::from([("foo".to_string(), foo), ("bar".to_string(), bar)]);
+
+        let act = find_runtime_collisions(&plugin_paths).unwrap();
+
+        let exp = BTreeMap::from([
+            ("lua/utils/init.lua".to_string(), vec!["bar", "foo"]),
+            ("plugin/common.vim".to_string(), vec!["bar", "foo"]),
+        ]);
+        assert_eq!(exp, act);
+    }
+}
@@ -6,23 +6,47 @@ pub mod dir {
     pub static POST_CONFIG: &str = "post_config";
     pub static DEPEND_PLUGINS: &str = "depend_plugins";
     pub static DEPEND_GROUPS: &str = "depend_groups";
-    pub static MODULES: &str = "modules";
     pub static EVENTS: &str = "events";
     pub static FILETYPES: &str = "filetypes";
     pub static COMMANDS: &str = "commands";
     pub static RTP: &str = "rtp";
     pub static AFTER: &str = "after";
     pub static FTPLUGIN: &str = "ftplugin";
+    pub static SYNTAX: &str = "syntax";
+    pub static INDENT: &str = "indent";
+    pub static QUERIES: &str = "queries";
+    pub static LSP: &str = "lsp";
     pub static INFO: &str = "info";
+    pub static DENOPS: &str = "denops";
+    pub static DOC: &str = "doc";
+    pub static EAGER: &str = "eager";
+    pub static FTDETECT: &str = "ftdetect";
+    pub static ROOT_MARKERS: &str = "root_markers";
+    pub static COLORSCHEMES: &str = "colorschemes";
+    pub static FUNCTIONS: &str = "functions";
 }
 
 pub mod file {
     pub static STARTUP_KEYS: &str = "startup_keys";
-    pub static MODULE_KEYS: &str = "module_keys";
+    pub static STARTUP_CONFIG: &str = "startup_config";
     pub static EVENT_KEYS: &str = "event_keys";
     pub static FILETYPE_KEYS: &str = "filetype_keys";
     pub static COMMAND_KEYS: &str = "command_keys";
+    pub static COMMAND_STUBS: &str = "command_stubs";
     pub static TIMER_CLIENTS: &str = "timer_clients";
     pub static DENOPS_CLIENTS: &str = "denops_clients";
     pub static BUNDLER_BIN: &str = "bundler_bin";
+    pub static HELP_TAGS: &str = "help_tags";
+    pub static TAGS: &str = "tags";
+    pub static LUA_MODULES: &str = "lua_modules";
+    pub static LUA_MODULE_OWNERS: &str = "lua_module_owners";
+    pub static FTDETECT_SCRIPT: &str = "bundler";
+    pub static LUA_MODULE: &str = "bundler.lua";
+    pub static JSON: &str = "bundle.json";
+    pub static STAMP: &str = ".bundler";
+    pub static ROOT_MARKER_KEYS: &str = "root_marker_keys";
+    pub static COLORSCHEME_KEYS: &str = "colorscheme_keys";
+    pub static FUNCTION_KEYS: &str = "function_keys";
+    pub static FOLLOWERS: &str = "followers";
+    pub static STARTUP_FOLLOWERS: &str = "startup_followers";
 }
@@ -1,52 +1,78 @@
-/* Rust friendly vim/neovim config. */
+//! Rust friendly vim/neovim config.
 
 mod common;
 mod config;
 mod from_target;
 mod id_table;
+mod infer;
+mod keymap;
+mod placeholder;
+mod setting;
 
+pub(crate) use crate::content::common::Collisions;
+pub use crate::content::common::Language;
 use crate::content::common::Target;
-pub use crate::content::config::{
-    AfterOption, Content, EagerPlugin, LazyGroup, LazyPlugin, Package, Info
+#[cfg(test)]
+pub(crate) use crate::content::config::LazyGroup;
+pub(crate) use crate::content::config::{
+    AfterCode, AfterOption, EagerPlugin, Info, LazyPlugin, Package,
 };
+pub use crate::content::config::{CommandRange, CommandStub, Content};
 use crate::content::from_target::FromTarget;
+use crate::content::infer::apply_inferred_triggers;
+use crate::content::placeholder::{Owner, Placeholders};
+use crate::content::setting::mk_setting_code;
 // TODO: capsule
-pub use crate::content::id_table::IdTable;
+pub(crate) use crate::content::id_table::IdTable;
 use crate::payload;
+use anyhow::Result;
 
-pub fn unpack(payload: payload::Payload) -> Content {
+/// resolve the payload for its target.
+///
+/// `root_dir` is the output directory substituted for `${bundler.root}` in configs.
+pub fn unpack(payload: payload::Payload, root_dir: Option<&str>) -> Result<Content> {
     let target = Target::from(payload.meta.target);
+    let id_table = IdTable::from(payload.meta.id_map);
+    let placeholders = Placeholders {
+        id_table: &id_table,
+        root_dir,
+    };
     let mut packages = payload
         .config
         .eager_plugins
         .into_iter()
-        .map(|p| Package::EagerPlugin(EagerPlugin::from_target(p, &target)))
-        .collect::<Vec<Package>>();
-    payload
-        .config
-        .lazy_plugins
-        .into_iter()
-        .map(|p| Vec::from_target(p, &target))
-        .flatten()
-        .for_each(|p| packages.push(p));
-    payload
-        .config
-        .lazy_groups
-        .into_iter()
-        .map(|p| Vec::from_target(p, &target))
-        .flatten()
-        .for_each(|p| packages.push(p));
-    let id_table = IdTable::from(payload.meta.id_map);
-    let after_option = AfterOption::from(payload.config.after);
+        .map(|p| EagerPlugin::from_target(p, &target, &placeholders).map(Package::EagerPlugin))
+        .collect::<Result<Vec<Package>>>()?;
+    for p in payload.config.lazy_plugins {
+        packages.extend(Vec::from_target(p, &target, &placeholders)?);
+    }
+    for p in payload.config.lazy_groups {
+        packages.extend(Vec::from_target(p, &target, &placeholders)?);
+    }
+    if payload.config.auto_triggers {
+        apply_inferred_triggers(&mut packages)?;
+    }
+    let after_option = AfterOption::from_target(payload.config.after, &target, &placeholders)?;
+
+    let startup_config = mk_setting_code(
+        &payload.config.options,
+        &payload.config.globals,
+        &target,
+        &Owner::Bundler,
+    )?;
 
     let info = Info {
         bundler_bin: payload.meta.bundler_bin,
     };
 
-    Content {
+    Ok(Content {
+        startup_config,
         packages,
         id_table,
         after_option,
         info,
-    }
+        prefer_eager: payload.config.prefer_eager,
+        collisions: Collisions::from(payload.config.collisions),
+        flatten_eager: payload.config.flatten_eager,
+    })
 }
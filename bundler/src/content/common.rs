@@ -1,4 +1,5 @@
 use crate::payload;
+use serde::Serialize;
 use std::fmt;
 
 #[derive(Debug, Eq, PartialEq)]
@@ -7,10 +8,24 @@ pub enum Target {
     Neovim,
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+/// how runtime file collisions between plugins are reported.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub enum Collisions {
+    /// not scanned.
+    Off,
+    #[default]
+    Warn,
+    Error,
+}
+
+/// language of a piece of code.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Language {
+    /// vim script.
     #[default]
     Vim,
+    /// lua, neovim only.
     Lua,
 }
 
@@ -41,6 +56,16 @@ impl From<payload::Language> for Language {
     }
 }
 
+impl From<payload::Collisions> for Collisions {
+    fn from(value: payload::Collisions) -> Self {
+        match value {
+            payload::Collisions::Off => Self::Off,
+            payload::Collisions::Warn => Self::Warn,
+            payload::Collisions::Error => Self::Error,
+        }
+    }
+}
+
 impl From<payload::Target> for Target {
     fn from(value: payload::Target) -> Self {
         match value {
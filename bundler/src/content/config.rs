@@ -1,8 +1,15 @@
-use crate::content::common::{Language, Target};
+use crate::content::common::{Collisions, Language, Target};
 use crate::content::from_target::FromTarget;
 use crate::content::id_table::IdTable;
+use crate::content::keymap::mk_keymap_code;
+use crate::content::placeholder::{Owner, Placeholders};
+use crate::content::setting::mk_setting_code;
 use crate::payload;
-use std::collections::HashMap;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct EagerPlugin {
@@ -22,8 +29,17 @@ pub struct LazyPlugin {
     pub on_events: Vec<String>,
     pub on_filetypes: Vec<String>,
     pub on_commands: Vec<String>,
+    pub command_stubs: Vec<CommandStub>,
+    /// glob patterns of autoload functions, e.g. `fzf#*`.
+    pub on_functions: Vec<String>,
+    /// nix packages of plugins after which this is loaded.
+    pub on_source_packages: Vec<String>,
+    pub on_colorschemes: Vec<String>,
+    pub on_root_markers: Vec<String>,
     pub is_timer_client: bool,
     pub is_denops_client: bool,
+    /// expanded from `dependPlugins`, not declared by the user.
+    pub is_dependency: bool,
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
@@ -40,7 +56,45 @@ pub struct LazyGroup {
     pub on_events: Vec<String>,
     pub on_filetypes: Vec<String>,
     pub on_commands: Vec<String>,
+    pub command_stubs: Vec<CommandStub>,
+    /// glob patterns of autoload functions, e.g. `fzf#*`.
+    pub on_functions: Vec<String>,
+    /// nix packages of plugins after which this is loaded.
+    pub on_source_packages: Vec<String>,
+    pub on_colorschemes: Vec<String>,
+    pub on_root_markers: Vec<String>,
     pub is_timer_client: bool,
+    pub is_denops_client: bool,
+}
+
+/// range accepted by a command stub.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CommandRange {
+    /// no range.
+    None,
+    /// `-range`
+    Line,
+    /// `-range=%`
+    File,
+    /// `-range=N`
+    Count(u32),
+}
+
+/// command defined before its plugin is loaded, so that it can be completed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandStub {
+    /// command name.
+    pub name: String,
+    /// `-nargs`, one of `0`, `1`, `*`, `?` and `+`.
+    pub nargs: String,
+    /// `-range`.
+    pub range: CommandRange,
+    /// `-bang`.
+    pub bang: bool,
+    /// `-complete`, empty for none.
+    pub complete: String,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -50,19 +104,45 @@ pub enum Package {
     LazyGroup(LazyGroup),
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AfterCode {
+    pub language: Language,
+    pub code: String,
+}
+
+#[derive(Default)]
 pub struct AfterOption {
-    pub ftplugin: HashMap<String, String>,
+    // at most one code per language.
+    pub ftplugin: HashMap<String, Vec<AfterCode>>,
+    pub plugin: HashMap<String, AfterCode>,
+    pub syntax: HashMap<String, AfterCode>,
+    pub indent: HashMap<String, AfterCode>,
+    pub lsp: HashMap<String, AfterCode>,
+    pub queries: HashMap<String, HashMap<String, String>>,
 }
 
 pub struct Info {
     pub bundler_bin: String,
 }
 
+/// the payload resolved for a target, made by [`crate::content::unpack`].
 pub struct Content {
+    /// code run at startup before the plugins, e.g. options and globals.
+    pub startup_config: String,
+    /// plugins and groups, with the ones they pull in expanded.
     pub packages: Vec<Package>,
+    /// plugin ids by nix package.
     pub id_table: IdTable,
+    /// files written under `after/`.
     pub after_option: AfterOption,
+    /// about the build.
     pub info: Info,
+    /// resolve plugins declared both eager and lazy in favor of eager.
+    pub prefer_eager: bool,
+    /// how runtime file collisions between plugins are reported.
+    pub collisions: Collisions,
+    /// merge eager plugins into a single runtime directory.
+    pub flatten_eager: bool,
 }
 
 fn mk_args_code(args: serde_json::Value, language: &Language) -> String {
@@ -89,77 +169,351 @@ fn mk_args_code(args: serde_json::Value, language: &Language) -> String {
     }
 }
 
-fn mk_simple_code(code: String, target: &Target) -> String {
+/// lua configs can not run on vim.
+fn lua_on_vim(owner: &Owner, file: Option<&str>) -> anyhow::Error {
+    match file {
+        Some(file) => anyhow!(
+            "lua config file `{}` of `{}` is not supported for vim.",
+            file,
+            owner
+        ),
+        None => anyhow!("lua config of `{}` is not supported for vim.", owner),
+    }
+}
+
+fn mk_simple_code(code: String, target: &Target, owner: &Owner) -> Result<String> {
     let language = Language::default();
-    if code == "" {
-        String::default()
+    if code.is_empty() {
+        Ok(String::default())
     } else {
         match (target, language) {
-            (Target::Vim, Language::Vim) => code,
-            (Target::Neovim, Language::Vim) => format!("vim.cmd([=[{}]=])", code),
-            (Target::Neovim, Language::Lua) => code,
-            _ => panic!("invalid target and language combination"),
+            (Target::Vim, Language::Vim) => Ok(code),
+            (Target::Neovim, Language::Vim) => Ok(format!("vim.cmd([=[{}]=])", code)),
+            (Target::Neovim, Language::Lua) => Ok(code),
+            _ => Err(lua_on_vim(owner, None)),
         }
     }
 }
 
-fn mk_detail_code(cfg: payload::DetailConfig, target: &Target) -> String {
+/// `file` is the config file the code was read from, if any.
+fn mk_detail_code(
+    cfg: payload::DetailConfig,
+    target: &Target,
+    owner: &Owner,
+    file: Option<&str>,
+) -> Result<String> {
     let language = Language::from(cfg.language);
     let args = mk_args_code(cfg.args, &language);
     match (target, language) {
-        (Target::Vim, Language::Vim) => format!("{}\n{}", args, cfg.code),
-        (Target::Neovim, Language::Vim) => format!("vim.cmd([=[\n{}\n{}]=])", args, cfg.code),
-        (Target::Neovim, Language::Lua) => format!("{}\n{}", args, cfg.code),
-        _ => panic!("invalid target and language combination"),
+        (Target::Vim, Language::Vim) => Ok(format!("{}\n{}", args, cfg.code)),
+        (Target::Neovim, Language::Vim) => Ok(format!("vim.cmd([=[\n{}\n{}]=])", args, cfg.code)),
+        (Target::Neovim, Language::Lua) => Ok(format!("{}\n{}", args, cfg.code)),
+        _ => Err(lua_on_vim(owner, file)),
+    }
+}
+
+/// read a config file of `owner`, inferring its language from the extension.
+fn read_config_file(cfg: payload::FileConfig, owner: &Owner) -> Result<payload::DetailConfig> {
+    let language = match cfg.language {
+        Some(language) => language,
+        None => match Path::new(&cfg.file)
+            .extension()
+            .and_then(|ext| ext.to_str())
+        {
+            Some("vim") => payload::Language::Vim,
+            Some("lua") => payload::Language::Lua,
+            _ => bail!(
+                "cannot infer the language of config file `{}` of `{}`.",
+                cfg.file,
+                owner
+            ),
+        },
+    };
+    let code = fs::read_to_string(&cfg.file)
+        .with_context(|| format!("failed to read config file `{}` of `{}`", cfg.file, owner))?;
+
+    Ok(payload::DetailConfig {
+        language,
+        code,
+        args: cfg.args,
+    })
+}
+
+fn mk_code(
+    cfg: payload::Config,
+    target: &Target,
+    placeholders: &Placeholders,
+    owner: &Owner,
+) -> Result<String> {
+    let (cfg, file) = match cfg {
+        payload::Config::Simple(code) => {
+            return mk_simple_code(placeholders.expand(&code, owner)?, target, owner)
+        }
+        payload::Config::Detail(cfg) => (cfg, None),
+        payload::Config::File(cfg) => {
+            let file = cfg.file.clone();
+            (read_config_file(cfg, owner)?, Some(file))
+        }
+    };
+    let code = placeholders.expand(&cfg.code, owner)?;
+
+    mk_detail_code(
+        payload::DetailConfig { code, ..cfg },
+        target,
+        owner,
+        file.as_deref(),
+    )
+}
+
+/// after files are sourced as is, so vim code is not wrapped for neovim.
+fn mk_after_code(
+    cfg: payload::Config,
+    target: &Target,
+    placeholders: &Placeholders,
+    owner: &Owner,
+) -> Result<AfterCode> {
+    let (cfg, file) = match cfg {
+        payload::Config::Simple(code) => {
+            return Ok(AfterCode {
+                language: Language::Vim,
+                code: placeholders.expand(&code, owner)?,
+            })
+        }
+        payload::Config::Detail(cfg) => (cfg, None),
+        payload::Config::File(cfg) => {
+            let file = cfg.file.clone();
+            (read_config_file(cfg, owner)?, Some(file))
+        }
+    };
+    let language = Language::from(cfg.language);
+    if *target == Target::Vim && language == Language::Lua {
+        return Err(lua_on_vim(owner, file.as_deref()));
+    }
+    let args = mk_args_code(cfg.args, &language);
+
+    Ok(AfterCode {
+        language,
+        code: format!("{}\n{}", args, placeholders.expand(&cfg.code, owner)?),
+    })
+}
+
+/// after file names become path components, so they must stay in their directory.
+fn check_after_name(name: &str, dir: &str) -> Result<()> {
+    if name.is_empty() || name.contains('/') || name.contains("..") {
+        bail!(
+            "invalid name `{}` in `after/{}`, which must not be empty or contain `/` or `..`.",
+            name,
+            dir
+        )
+    }
+
+    Ok(())
+}
+
+/// root markers become paths under `root_markers/`, so they must stay in that directory.
+fn check_root_markers(markers: &[String], owner: &Owner) -> Result<()> {
+    for marker in markers {
+        if marker.split('/').any(|c| c.is_empty() || c == "..") {
+            bail!(
+                "invalid root marker `{}` of `{}`, which must not be empty or contain empty or `..` components.",
+                marker,
+                owner
+            )
+        }
+    }
+
+    Ok(())
+}
+
+fn mk_after_codes(
+    cfgs: HashMap<String, payload::Config>,
+    target: &Target,
+    placeholders: &Placeholders,
+    dir: &str,
+) -> Result<HashMap<String, AfterCode>> {
+    cfgs.into_iter()
+        .map(|(name, cfg)| {
+            check_after_name(&name, dir)?;
+            let owner = Owner::After(format!("after/{}/{}", dir, name));
+            let code = mk_after_code(cfg, target, placeholders, &owner)?;
+            Ok((name, code))
+        })
+        .collect()
+}
+
+/// append generated code to a config stage.
+fn append_code(code: String, extra: String) -> String {
+    if code.is_empty() {
+        extra
+    } else if extra.is_empty() {
+        code
+    } else {
+        format!("{}\n{}", code, extra)
+    }
+}
+
+/// keymap code for the startup and post config stages.
+///
+/// global keymaps are defined at startup, so that they load the plugin through its triggers.
+/// buffer-local ones are defined in the buffer the plugin is loaded for.
+fn mk_keymap_codes(
+    keymaps: &[payload::Keymap],
+    target: &Target,
+    owner: &Owner,
+) -> Result<(String, String)> {
+    let (buffer_keymaps, keymaps): (Vec<_>, Vec<_>) = keymaps.iter().partition(|k| k.buffer);
+
+    Ok((
+        mk_keymap_code(&keymaps, target, owner)?,
+        mk_keymap_code(&buffer_keymaps, target, owner)?,
+    ))
+}
+
+/// names of command triggers, and stubs of the structured ones.
+fn mk_command_triggers(
+    triggers: Vec<payload::CommandTrigger>,
+    owner: &Owner,
+) -> Result<(Vec<String>, Vec<CommandStub>)> {
+    let mut names = vec![];
+    let mut stubs = vec![];
+    for trigger in triggers {
+        let stub = match trigger {
+            payload::CommandTrigger::Name(name) => {
+                names.push(name);
+                continue;
+            }
+            payload::CommandTrigger::Stub(stub) => stub,
+        };
+        if !["0", "1", "*", "?", "+"].contains(&stub.nargs.as_str()) {
+            bail!(
+                "command `{}` of `{}` has an invalid nargs `{}`.",
+                stub.name,
+                owner,
+                stub.nargs
+            );
+        }
+        if stub.nargs == "0" && !stub.complete.is_empty() {
+            bail!(
+                "command `{}` of `{}` is completed but takes no arguments.",
+                stub.name,
+                owner
+            );
+        }
+        let range = match stub.range {
+            payload::CommandRange::Enabled(false) => CommandRange::None,
+            payload::CommandRange::Enabled(true) => CommandRange::Line,
+            payload::CommandRange::Count(n) => CommandRange::Count(n),
+            payload::CommandRange::Whole(range) if range == "%" => CommandRange::File,
+            payload::CommandRange::Whole(range) => bail!(
+                "command `{}` of `{}` has an invalid range `{}`.",
+                stub.name,
+                owner,
+                range
+            ),
+        };
+        names.push(stub.name.clone());
+        stubs.push(CommandStub {
+            name: stub.name,
+            nargs: stub.nargs,
+            range,
+            bang: stub.bang,
+            complete: stub.complete,
+        });
     }
+
+    Ok((names, stubs))
+}
+
+/// merge codes written in the same language, vim first.
+fn merge_after_codes(codes: Vec<AfterCode>) -> Vec<AfterCode> {
+    let mut merged: BTreeMap<Language, Vec<String>> = BTreeMap::new();
+    for code in codes {
+        merged.entry(code.language).or_default().push(code.code);
+    }
+    merged
+        .into_iter()
+        .map(|(language, codes)| AfterCode {
+            language,
+            code: codes.join("\n"),
+        })
+        .collect()
 }
 
 impl FromTarget<payload::EagerVimPluginPackage> for EagerPlugin {
-    fn from_target(value: payload::EagerVimPluginPackage, target: &Target) -> Self {
-        match value {
+    fn from_target(
+        value: payload::EagerVimPluginPackage,
+        target: &Target,
+        placeholders: &Placeholders,
+    ) -> Result<Self> {
+        Ok(match value {
             payload::EagerVimPluginPackage::SimplePackage(pkg) => EagerPlugin {
                 nix_package: pkg,
                 ..Default::default()
             },
             payload::EagerVimPluginPackage::ConfiguredPackage(cfg) => {
-                let startup_config = match cfg.startup_config {
-                    payload::Config::Simple(code) => mk_simple_code(code, target),
-                    payload::Config::Detail(cfg) => mk_detail_code(cfg, target),
-                };
+                let owner = placeholders.plugin_owner(&cfg.plugin);
+                let startup_config = append_code(
+                    mk_setting_code(&cfg.options, &cfg.globals, target, &owner)?,
+                    mk_code(cfg.startup_config, target, placeholders, &owner)?,
+                );
+                let keymaps = cfg.keymaps.iter().collect::<Vec<_>>();
+                let startup_config =
+                    append_code(startup_config, mk_keymap_code(&keymaps, target, &owner)?);
                 EagerPlugin {
                     nix_package: cfg.plugin,
                     startup_config,
                 }
             }
+        })
+    }
+}
+
+/// packages of a plugin pulled in by another package.
+fn mk_dependency_packages(
+    value: payload::LazyVimPluginPackage,
+    target: &Target,
+    placeholders: &Placeholders,
+) -> Result<Vec<Package>> {
+    let mut packages = Vec::from_target(value, target, placeholders)?;
+    for package in &mut packages {
+        if let Package::LazyPlugin(p) = package {
+            p.is_dependency = true;
         }
     }
+
+    Ok(packages)
 }
 
 impl FromTarget<payload::LazyVimPluginPackage> for Vec<Package> {
-    fn from_target(value: payload::LazyVimPluginPackage, target: &Target) -> Self {
+    fn from_target(
+        value: payload::LazyVimPluginPackage,
+        target: &Target,
+        placeholders: &Placeholders,
+    ) -> Result<Self> {
         match value {
             payload::LazyVimPluginPackage::SimplePackage(pkg) => {
-                vec![Package::LazyPlugin(LazyPlugin {
+                Ok(vec![Package::LazyPlugin(LazyPlugin {
                     nix_package: pkg,
                     ..Default::default()
-                })]
+                })])
             }
             payload::LazyVimPluginPackage::ConfiguredPackage(cfg) => {
                 let mut packages = vec![];
 
                 // package
-                let startup_config = match cfg.startup_config {
-                    payload::Config::Simple(code) => mk_simple_code(code, target),
-                    payload::Config::Detail(cfg) => mk_detail_code(cfg, target),
-                };
-                let pre_config = match cfg.pre_config {
-                    payload::Config::Simple(code) => mk_simple_code(code, target),
-                    payload::Config::Detail(cfg) => mk_detail_code(cfg, target),
-                };
-                let post_config = match cfg.post_config {
-                    payload::Config::Simple(code) => mk_simple_code(code, target),
-                    payload::Config::Detail(cfg) => mk_detail_code(cfg, target),
-                };
+                let owner = placeholders.plugin_owner(&cfg.plugin);
+                let startup_config = append_code(
+                    mk_setting_code(&cfg.options, &cfg.globals, target, &owner)?,
+                    mk_code(cfg.startup_config, target, placeholders, &owner)?,
+                );
+                let pre_config = mk_code(cfg.pre_config, target, placeholders, &owner)?;
+                let post_config = mk_code(cfg.post_config, target, placeholders, &owner)?;
+                let (startup_keymaps, post_keymaps) =
+                    mk_keymap_codes(&cfg.keymaps, target, &owner)?;
+                let startup_config = append_code(startup_config, startup_keymaps);
+                let post_config = append_code(post_config, post_keymaps);
+                let (on_commands, command_stubs) = mk_command_triggers(cfg.on_commands, &owner)?;
+                check_root_markers(&cfg.on_root_markers, &owner)?;
                 let depend_plugins = cfg
                     .depend_plugins
                     .iter()
@@ -178,29 +532,35 @@ impl FromTarget<payload::LazyVimPluginPackage> for Vec<Package> {
                     on_modules: cfg.on_modules,
                     on_events: cfg.on_events,
                     on_filetypes: cfg.on_filetypes,
-                    on_commands: cfg.on_commands,
+                    on_commands,
+                    command_stubs,
+                    on_functions: cfg.on_functions,
+                    on_source_packages: [cfg.on_sources, cfg.on_plugins].concat(),
+                    on_colorschemes: cfg.on_colorschemes,
+                    on_root_markers: cfg.on_root_markers,
                     is_timer_client: cfg.use_timer,
                     is_denops_client: cfg.use_denops,
+                    is_dependency: false,
                 };
                 packages.push(Package::LazyPlugin(plugin));
 
                 // depend packages
-                let depend_packages = cfg
-                    .depend_plugins
-                    .into_iter()
-                    .map(|p| Vec::from_target(p, target))
-                    .flatten()
-                    .collect::<Vec<Package>>();
-                packages.extend(depend_packages);
+                for p in cfg.depend_plugins {
+                    packages.extend(mk_dependency_packages(p, target, placeholders)?);
+                }
 
-                packages
+                Ok(packages)
             }
         }
     }
 }
 
 impl FromTarget<payload::LazyGroup> for Vec<Package> {
-    fn from_target(value: payload::LazyGroup, target: &Target) -> Self {
+    fn from_target(
+        value: payload::LazyGroup,
+        target: &Target,
+        placeholders: &Placeholders,
+    ) -> Result<Self> {
         let mut packages = vec![];
 
         // package
@@ -212,18 +572,15 @@ impl FromTarget<payload::LazyGroup> for Vec<Package> {
                 payload::LazyVimPluginPackage::ConfiguredPackage(cfg) => cfg.plugin.clone(),
             })
             .collect();
-        let startup_config = match value.startup_config {
-            payload::Config::Simple(code) => mk_simple_code(code, target),
-            payload::Config::Detail(cfg) => mk_detail_code(cfg, target),
-        };
-        let pre_config = match value.pre_config {
-            payload::Config::Simple(code) => mk_simple_code(code, target),
-            payload::Config::Detail(cfg) => mk_detail_code(cfg, target),
-        };
-        let post_config = match value.post_config {
-            payload::Config::Simple(code) => mk_simple_code(code, target),
-            payload::Config::Detail(cfg) => mk_detail_code(cfg, target),
-        };
+        let owner = Owner::Group(&value.name);
+        let startup_config = mk_code(value.startup_config, target, placeholders, &owner)?;
+        let pre_config = mk_code(value.pre_config, target, placeholders, &owner)?;
+        let post_config = mk_code(value.post_config, target, placeholders, &owner)?;
+        let (startup_keymaps, post_keymaps) = mk_keymap_codes(&value.keymaps, target, &owner)?;
+        let startup_config = append_code(startup_config, startup_keymaps);
+        let post_config = append_code(post_config, post_keymaps);
+        let (on_commands, command_stubs) = mk_command_triggers(value.on_commands, &owner)?;
+        check_root_markers(&value.on_root_markers, &owner)?;
         let depend_plugins = value
             .depend_plugins
             .iter()
@@ -243,37 +600,318 @@ impl FromTarget<payload::LazyGroup> for Vec<Package> {
             on_modules: value.on_modules,
             on_events: value.on_events,
             on_filetypes: value.on_filetypes,
-            on_commands: value.on_commands,
+            on_commands,
+            command_stubs,
+            on_functions: value.on_functions,
+            on_source_packages: [value.on_sources, value.on_plugins].concat(),
+            on_colorschemes: value.on_colorschemes,
+            on_root_markers: value.on_root_markers,
             is_timer_client: value.use_timer,
+            is_denops_client: value.use_denops,
         };
         packages.push(Package::LazyGroup(group));
 
         // plugin packages
-        let plugin_packages = value
-            .plugins
-            .into_iter()
-            .map(|p| Vec::from_target(p, target))
-            .flatten()
-            .collect::<Vec<Package>>();
-        packages.extend(plugin_packages);
+        for p in value.plugins {
+            packages.extend(Vec::from_target(p, target, placeholders)?);
+        }
 
         // depend packages
-        let depend_packages = value
-            .depend_plugins
-            .into_iter()
-            .map(|p| Vec::from_target(p, target))
-            .flatten()
-            .collect::<Vec<Package>>();
-        packages.extend(depend_packages);
+        for p in value.depend_plugins {
+            packages.extend(mk_dependency_packages(p, target, placeholders)?);
+        }
+
+        Ok(packages)
+    }
+}
+
+impl FromTarget<payload::AfterOption> for AfterOption {
+    fn from_target(
+        value: payload::AfterOption,
+        target: &Target,
+        placeholders: &Placeholders,
+    ) -> Result<Self> {
+        let mut ftplugin = HashMap::new();
+        for (filetype, cfgs) in value.ftplugin {
+            check_after_name(&filetype, "ftplugin")?;
+            let owner = Owner::After(format!("after/ftplugin/{}", filetype));
+            let codes = cfgs
+                .into_vec()
+                .into_iter()
+                .map(|cfg| mk_after_code(cfg, target, placeholders, &owner))
+                .collect::<Result<Vec<_>>>()?;
+            ftplugin.insert(filetype, merge_after_codes(codes));
+        }
+        for (language, queries) in &value.queries {
+            check_after_name(language, "queries")?;
+            for name in queries.keys() {
+                check_after_name(name, &format!("queries/{}", language))?;
+            }
+        }
 
-        packages
+        Ok(AfterOption {
+            ftplugin,
+            plugin: mk_after_codes(value.plugin, target, placeholders, "plugin")?,
+            syntax: mk_after_codes(value.syntax, target, placeholders, "syntax")?,
+            indent: mk_after_codes(value.indent, target, placeholders, "indent")?,
+            lsp: mk_after_codes(value.lsp, target, placeholders, "lsp")?,
+            queries: value.queries,
+        })
     }
 }
 
-impl From<payload::AfterOption> for AfterOption {
-    fn from(value: payload::AfterOption) -> Self {
-        AfterOption {
-            ftplugin: value.ftplugin,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp::TempDir;
+    use rstest::rstest;
+
+    fn vim(code: &str) -> AfterCode {
+        AfterCode {
+            language: Language::Vim,
+            code: code.to_string(),
+        }
+    }
+
+    fn lua(code: &str) -> AfterCode {
+        AfterCode {
+            language: Language::Lua,
+            code: code.to_string(),
         }
     }
+
+    #[rstest(arg, exp,
+        case(vec![], vec![]),
+        case(vec![vim("a")], vec![vim("a")]),
+        case(vec![vim("a"), vim("b")], vec![vim("a\nb")]),
+        case(vec![lua("a"), vim("b"), lua("c")], vec![vim("b"), lua("a\nc")]),
+    )]
+    fn test_merge_after_codes(arg: Vec<AfterCode>, exp: Vec<AfterCode>) {
+        let act = merge_after_codes(arg);
+
+        assert_eq!(exp, act);
+    }
+
+    #[rstest(
+        file,
+        language,
+        exp,
+        case("a.lua", None, Some(payload::Language::Lua)),
+        case("a.vim", None, Some(payload::Language::Vim)),
+        case("a.txt", Some(payload::Language::Lua), Some(payload::Language::Lua)),
+        case("a.txt", None, None)
+    )]
+    fn test_read_config_file(
+        file: &str,
+        language: Option<payload::Language>,
+        exp: Option<payload::Language>,
+    ) {
+        let root = TempDir::new("config");
+        let path = root.write(file, "code");
+        let cfg = payload::FileConfig {
+            language,
+            file: path.to_string_lossy().to_string(),
+            args: serde_json::Value::Null,
+        };
+
+        let act = read_config_file(
+            cfg,
+            &Owner::Plugin {
+                id: "foo",
+                package: "/nix/store/foo",
+            },
+        );
+
+        match exp {
+            Some(language) => {
+                let act = act.unwrap();
+                assert_eq!(language, act.language);
+                assert_eq!("code", act.code);
+            }
+            None => assert!(act.is_err()),
+        }
+    }
+
+    #[test]
+    fn test_read_config_file_missing() {
+        let cfg = payload::FileConfig {
+            language: None,
+            file: "/nonexistent/bundler/foo.lua".to_string(),
+            args: serde_json::Value::Null,
+        };
+
+        let act = read_config_file(
+            cfg,
+            &Owner::Plugin {
+                id: "foo",
+                package: "/nix/store/foo",
+            },
+        )
+        .unwrap_err();
+
+        assert!(act.to_string().contains("`foo`"));
+    }
+
+    #[test]
+    fn test_lua_config_on_vim() {
+        let root = TempDir::new("lua-on-vim");
+        let file = root.write("foo.lua", "code").to_string_lossy().to_string();
+        let id_table = IdTable::from(vec![payload::IdMapElement {
+            plugin_id: "foo".to_string(),
+            package: "/nix/store/foo".to_string(),
+        }]);
+        let placeholders = placeholders(&id_table);
+        let owner = placeholders.plugin_owner("/nix/store/foo");
+        let cfg = || {
+            payload::Config::File(payload::FileConfig {
+                language: None,
+                file: file.clone(),
+                args: serde_json::Value::Null,
+            })
+        };
+
+        let code = mk_code(cfg(), &Target::Vim, &placeholders, &owner).unwrap_err();
+        let after_code = mk_after_code(cfg(), &Target::Vim, &placeholders, &owner).unwrap_err();
+
+        let exp = format!(
+            "lua config file `{}` of `foo` is not supported for vim.",
+            file
+        );
+        assert_eq!(exp, code.to_string());
+        assert_eq!(exp, after_code.to_string());
+    }
+
+    fn stub(nargs: &str, range: payload::CommandRange, complete: &str) -> payload::CommandTrigger {
+        payload::CommandTrigger::Stub(payload::CommandStub {
+            name: "Foo".to_string(),
+            nargs: nargs.to_string(),
+            range,
+            bang: true,
+            complete: complete.to_string(),
+        })
+    }
+
+    #[rstest(arg, exp,
+        case(payload::CommandTrigger::Name("Foo".to_string()), Some(None)),
+        case(stub("*", payload::CommandRange::Enabled(false), "file"), Some(Some(CommandRange::None))),
+        case(stub("?", payload::CommandRange::Enabled(true), ""), Some(Some(CommandRange::Line))),
+        case(stub("1", payload::CommandRange::Whole("%".to_string()), ""), Some(Some(CommandRange::File))),
+        case(stub("+", payload::CommandRange::Count(3), ""), Some(Some(CommandRange::Count(3)))),
+        case(stub("2", payload::CommandRange::Enabled(false), ""), None),
+        case(stub("0", payload::CommandRange::Enabled(false), "file"), None),
+        case(stub("0", payload::CommandRange::Whole("$".to_string()), ""), None),
+    )]
+    fn test_mk_command_triggers(arg: payload::CommandTrigger, exp: Option<Option<CommandRange>>) {
+        let act = mk_command_triggers(
+            vec![arg],
+            &Owner::Plugin {
+                id: "foo",
+                package: "/nix/store/foo",
+            },
+        );
+
+        match exp {
+            Some(range) => {
+                let (names, stubs) = act.unwrap();
+                assert_eq!(vec!["Foo".to_string()], names);
+                assert_eq!(range, stubs.first().map(|stub| stub.range.clone()));
+            }
+            None => assert!(act.is_err()),
+        }
+    }
+
+    #[rstest(
+        marker,
+        valid,
+        case("Cargo.toml", true),
+        case(".git/config", true),
+        case("", false),
+        case("/etc/passwd", false),
+        case(".git/", false),
+        case("../startup_keys", false),
+        case("a/../../b", false)
+    )]
+    fn test_check_root_markers(marker: &str, valid: bool) {
+        let act = check_root_markers(&[marker.to_string()], &Owner::Group("foo"));
+
+        assert_eq!(valid, act.is_ok());
+    }
+
+    fn placeholders(id_table: &IdTable) -> Placeholders<'_> {
+        Placeholders {
+            id_table,
+            root_dir: None,
+        }
+    }
+
+    fn detail(language: payload::Language, code: &str) -> payload::Config {
+        payload::Config::Detail(payload::DetailConfig {
+            language,
+            code: code.to_string(),
+            args: serde_json::json!({"a": 1}),
+        })
+    }
+
+    #[rstest(cfg, exp,
+        case(payload::Config::Simple("a".to_string()), vim("a")),
+        case(detail(payload::Language::Vim, "a"), vim("let s:args = json_decode('{\"a\":1}')\na")),
+        case(detail(payload::Language::Lua, "a"), lua("local args = vim.json.decode([[{\"a\":1}]])\na")),
+    )]
+    fn test_mk_after_code(cfg: payload::Config, exp: AfterCode) {
+        let id_table = IdTable::from(vec![]);
+        let owner = Owner::After("after/plugin/foo".to_string());
+
+        let act = mk_after_code(cfg, &Target::Neovim, &placeholders(&id_table), &owner).unwrap();
+
+        assert_eq!(exp, act);
+    }
+
+    #[rstest(
+        dir,
+        name,
+        valid,
+        case("plugin", "foo", true),
+        case("syntax", "foo.bar", true),
+        case("indent", "../foo", false),
+        case("lsp", "foo/bar", false),
+        case("ftplugin", "..", false),
+        case("queries", "", false)
+    )]
+    fn test_mk_after_codes(dir: &str, name: &str, valid: bool) {
+        let id_table = IdTable::from(vec![]);
+        let cfgs = HashMap::from([(name.to_string(), payload::Config::Simple("a".to_string()))]);
+
+        let act = mk_after_codes(cfgs, &Target::Neovim, &placeholders(&id_table), dir);
+
+        match valid {
+            true => assert_eq!(vim("a"), act.unwrap()[name]),
+            false => assert!(act.is_err()),
+        }
+    }
+
+    #[rstest(ftplugin, queries, valid,
+        case("nix", ("lua", "highlights"), true),
+        case("../nix", ("lua", "highlights"), false),
+        case("nix", ("../lua", "highlights"), false),
+        case("nix", ("lua", "a/highlights"), false),
+    )]
+    fn test_after_option_names(ftplugin: &str, queries: (&str, &str), valid: bool) {
+        let id_table = IdTable::from(vec![]);
+        let (language, query) = queries;
+        let value = payload::AfterOption {
+            ftplugin: HashMap::from([(
+                ftplugin.to_string(),
+                payload::Configs::Many(vec![detail(payload::Language::Lua, "a")]),
+            )]),
+            queries: HashMap::from([(
+                language.to_string(),
+                HashMap::from([(query.to_string(), "(a)".to_string())]),
+            )]),
+            ..Default::default()
+        };
+
+        let act = AfterOption::from_target(value, &Target::Neovim, &placeholders(&id_table));
+
+        assert_eq!(valid, act.is_ok());
+    }
 }
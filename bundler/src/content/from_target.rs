@@ -1,5 +1,7 @@
 use crate::content::common::Target;
+use crate::content::placeholder::Placeholders;
+use anyhow::Result;
 
 pub trait FromTarget<T>: Sized {
-    fn from_target(value: T, target: &Target) -> Self;
+    fn from_target(value: T, target: &Target, placeholders: &Placeholders) -> Result<Self>;
 }
@@ -11,7 +11,14 @@ impl IdTable {
     ///
     /// precondition: the key must be the value registered at initialization.
     pub fn get<T: TableKey + ?Sized>(&self, key: &T) -> &str {
-        &self.value.get(key.key()).expect("unregistered key was used")
+        self.value
+            .get(key.key())
+            .expect("unregistered key was used")
+    }
+
+    /// get plugin_id, or `None` if the key is not registered.
+    pub fn find<T: TableKey + ?Sized>(&self, key: &T) -> Option<&str> {
+        self.value.get(key.key()).map(|id| id.as_str())
     }
 }
 
@@ -0,0 +1,201 @@
+use crate::content::config::Package;
+use crate::util::file::{read_dir_entries, read_files_recursive};
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// triggers derived from a plugin tree.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct InferredTriggers {
+    pub modules: Vec<String>,
+    pub commands: Vec<String>,
+    pub filetypes: Vec<String>,
+}
+
+impl InferredTriggers {
+    fn extend(&mut self, other: InferredTriggers) {
+        self.modules.extend(other.modules);
+        self.commands.extend(other.commands);
+        self.filetypes.extend(other.filetypes);
+    }
+}
+
+/// `command[!] [-attr...] Name ...` in vim script, including abbreviations down to `com`.
+fn parse_vim_commands(code: &str) -> Vec<String> {
+    code.lines()
+        .filter_map(|line| {
+            let line = line.trim_start().trim_start_matches(':');
+            let mut tokens = line.split_whitespace();
+            let command = tokens.next()?;
+            let command = command.strip_suffix('!').unwrap_or(command);
+            if command.len() < 3 || !"command".starts_with(command) {
+                return None;
+            }
+            tokens
+                .find(|token| !token.starts_with('-'))
+                .filter(|name| name.starts_with(|c: char| c.is_ascii_uppercase()))
+                .map(|name| name.to_string())
+        })
+        .collect()
+}
+
+/// `nvim_create_user_command("Name", ...)` in lua.
+fn parse_lua_commands(code: &str) -> Vec<String> {
+    code.split("nvim_create_user_command(")
+        .skip(1)
+        .filter_map(|rest| {
+            let rest = rest.trim_start();
+            let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let name = rest[1..].split(quote).next()?;
+            if name.starts_with(|c: char| c.is_ascii_uppercase()) {
+                Some(name.to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// `ftplugin/<ft>.vim`, `ftplugin/<ft>_<name>.lua`, `ftplugin/<ft>/...`.
+fn parse_ftplugin_name(name: &str, is_dir: bool) -> Option<String> {
+    let stem = if is_dir {
+        name
+    } else {
+        name.strip_suffix(".vim")
+            .or_else(|| name.strip_suffix(".lua"))?
+    };
+    let filetype = stem.split('_').next().unwrap_or(stem);
+    if filetype.is_empty() {
+        None
+    } else {
+        Some(filetype.to_string())
+    }
+}
+
+/// derive triggers from the plugin tree.
+pub fn infer_triggers(plugin_path: &str) -> Result<InferredTriggers> {
+    let root = Path::new(plugin_path);
+    let mut triggers = InferredTriggers::default();
+
+    for (name, path) in read_dir_entries(&root.join("lua"))? {
+        if path.is_dir() {
+            triggers.modules.push(name);
+        } else if let Some(module) = name.strip_suffix(".lua") {
+            triggers.modules.push(module.to_string());
+        }
+    }
+
+    for path in read_files_recursive(&root.join("plugin"))? {
+        let read = |path: &Path| -> Result<String> {
+            Ok(String::from_utf8_lossy(&fs::read(path)?).to_string())
+        };
+        let commands = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("vim") => parse_vim_commands(&read(&path)?),
+            Some("lua") => parse_lua_commands(&read(&path)?),
+            _ => continue,
+        };
+        triggers.commands.extend(commands);
+    }
+
+    for (name, path) in read_dir_entries(&root.join("ftplugin"))? {
+        if let Some(filetype) = parse_ftplugin_name(&name, path.is_dir()) {
+            triggers.filetypes.push(filetype);
+        }
+    }
+
+    triggers.modules.sort();
+    triggers.modules.dedup();
+    triggers.commands.sort();
+    triggers.commands.dedup();
+    triggers.filetypes.sort();
+    triggers.filetypes.dedup();
+
+    Ok(triggers)
+}
+
+/// merge inferred triggers into lazy plugins and groups.
+///
+/// plugins belonging to a group are attributed to the group.
+pub fn apply_inferred_triggers(packages: &mut [Package]) -> Result<()> {
+    let group_plugins = packages
+        .iter()
+        .filter_map(|package| match package {
+            Package::LazyGroup(g) => Some(g.plugins.clone()),
+            _ => None,
+        })
+        .flatten()
+        .collect::<HashSet<String>>();
+
+    for package in packages.iter_mut() {
+        match package {
+            Package::LazyPlugin(p) if !group_plugins.contains(&p.nix_package) => {
+                let triggers = infer_triggers(&p.nix_package)?;
+                log::debug!("inferred triggers of `{}`: {:?}", p.nix_package, triggers);
+                p.on_modules.extend(triggers.modules);
+                p.on_commands.extend(triggers.commands);
+                p.on_filetypes.extend(triggers.filetypes);
+            }
+            Package::LazyGroup(g) => {
+                let mut triggers = InferredTriggers::default();
+                for plugin in &g.plugins {
+                    triggers.extend(infer_triggers(plugin)?);
+                }
+                log::debug!("inferred triggers of `{}`: {:?}", g.name, triggers);
+                g.on_modules.extend(triggers.modules);
+                g.on_commands.extend(triggers.commands);
+                g.on_filetypes.extend(triggers.filetypes);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(arg, exp,
+        case("", vec![]),
+        case("command! Foo call foo#run()", vec!["Foo"]),
+        case("  command! -nargs=* -bang -complete=file FooBar call foo#run(<q-args>)", vec!["FooBar"]),
+        case(":command! -range Baz echo 1\ncommand Qux echo 1", vec!["Baz", "Qux"]),
+        case("com! Foo echo 1\ncomm -bar Bar echo 1\ncomma! Baz echo 1", vec!["Foo", "Bar", "Baz"]),
+        case("command! -nargs=0 lower echo 1", vec![]),
+        case("co! Foo echo 1\ncomclear\ncompiler! Foo\ncommand", vec![]),
+    )]
+    fn test_parse_vim_commands(arg: &str, exp: Vec<&str>) {
+        let act = parse_vim_commands(arg);
+
+        assert_eq!(exp, act);
+    }
+
+    #[rstest(arg, exp,
+        case("", vec![]),
+        case(r#"vim.api.nvim_create_user_command("Foo", function() end, {})"#, vec!["Foo"]),
+        case("api.nvim_create_user_command( 'Bar', run, {})\nnvim_create_user_command(name, run, {})", vec!["Bar"]),
+    )]
+    fn test_parse_lua_commands(arg: &str, exp: Vec<&str>) {
+        let act = parse_lua_commands(arg);
+
+        assert_eq!(exp, act);
+    }
+
+    #[rstest(
+        name,
+        is_dir,
+        exp,
+        case("rust.vim", false, Some("rust")),
+        case("rust_extra.lua", false, Some("rust")),
+        case("markdown", true, Some("markdown")),
+        case("README.md", false, None)
+    )]
+    fn test_parse_ftplugin_name(name: &str, is_dir: bool, exp: Option<&str>) {
+        let act = parse_ftplugin_name(name, is_dir);
+
+        assert_eq!(exp.map(String::from), act);
+    }
+}
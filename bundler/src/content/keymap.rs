@@ -0,0 +1,174 @@
+use crate::content::common::Target;
+use crate::content::placeholder::Owner;
+use crate::payload;
+use crate::util::lua::to_lua_string;
+use anyhow::{bail, Result};
+
+/// short mode names accepted by both `:map` and `vim.keymap.set`.
+const MODES: [&str; 11] = ["", "!", "n", "v", "x", "s", "o", "i", "l", "c", "t"];
+
+/// `{mode}noremap [<buffer>] [<silent>] {lhs} {rhs}` for each mode.
+fn mk_vim_keymap(keymap: &payload::Keymap, owner: &Owner) -> Result<Vec<String>> {
+    let rhs = match &keymap.rhs {
+        payload::KeymapRhs::Command(rhs) => rhs,
+        payload::KeymapRhs::Lua(_) => bail!(
+            "keymap `{}` of `{}` has a lua rhs, which vim does not support.",
+            keymap.lhs,
+            owner
+        ),
+    };
+    let mut args = String::new();
+    if keymap.buffer {
+        args.push_str("<buffer> ");
+    }
+    if keymap.silent {
+        args.push_str("<silent> ");
+    }
+    // `|` ends the command and a space ends `{lhs}`.
+    let lhs = keymap.lhs.replace('|', "<Bar>").replace(' ', "<Space>");
+    let rhs = rhs.replace('|', "<Bar>");
+
+    Ok(keymap
+        .mode
+        .iter()
+        .map(|mode| match mode.as_str() {
+            "!" => format!("noremap! {}{} {}", args, lhs, rhs),
+            _ => format!("{}noremap {}{} {}", mode, args, lhs, rhs),
+        })
+        .collect())
+}
+
+/// `vim.keymap.set({modes}, {lhs}, {rhs}, {opts})`.
+fn mk_lua_keymap(keymap: &payload::Keymap) -> String {
+    let modes = keymap
+        .mode
+        .iter()
+        .map(|mode| to_lua_string(mode))
+        .collect::<Vec<_>>();
+    let rhs = match &keymap.rhs {
+        payload::KeymapRhs::Command(rhs) => to_lua_string(rhs),
+        payload::KeymapRhs::Lua(rhs) => rhs.lua.clone(),
+    };
+    let mut opts = vec![];
+    if !keymap.desc.is_empty() {
+        opts.push(format!("desc={}", to_lua_string(&keymap.desc)));
+    }
+    if keymap.silent {
+        opts.push("silent=true".to_string());
+    }
+    if keymap.buffer {
+        opts.push("buffer=true".to_string());
+    }
+
+    format!(
+        "vim.keymap.set({{{}}}, {}, {}, {{{}}})",
+        modes.join(","),
+        to_lua_string(&keymap.lhs),
+        rhs,
+        opts.join(",")
+    )
+}
+
+/// code defining `keymaps` of `owner` for the target.
+pub fn mk_keymap_code(
+    keymaps: &[&payload::Keymap],
+    target: &Target,
+    owner: &Owner,
+) -> Result<String> {
+    let mut lines = vec![];
+    for keymap in keymaps {
+        if let Some(mode) = keymap.mode.iter().find(|m| !MODES.contains(&m.as_str())) {
+            bail!(
+                "keymap `{}` of `{}` has an unknown mode `{}`.",
+                keymap.lhs,
+                owner,
+                mode
+            );
+        }
+        match target {
+            Target::Vim => lines.extend(mk_vim_keymap(keymap, owner)?),
+            Target::Neovim => lines.push(mk_lua_keymap(keymap)),
+        }
+    }
+
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn keymap(mode: &[&str], lhs: &str, rhs: payload::KeymapRhs) -> payload::Keymap {
+        payload::Keymap {
+            mode: mode.iter().map(|m| m.to_string()).collect(),
+            lhs: lhs.to_string(),
+            rhs,
+            desc: String::default(),
+            silent: false,
+            buffer: false,
+        }
+    }
+
+    fn command(rhs: &str) -> payload::KeymapRhs {
+        payload::KeymapRhs::Command(rhs.to_string())
+    }
+
+    fn lua(rhs: &str) -> payload::KeymapRhs {
+        payload::KeymapRhs::Lua(payload::LuaRhs {
+            lua: rhs.to_string(),
+        })
+    }
+
+    #[rstest(arg, target, exp,
+        case(keymap(&["n"], "<leader>f", command(":Files<CR>")), Target::Vim, Some("nnoremap <leader>f :Files<CR>")),
+        case(keymap(&["n", "x"], "a b", command("c|d")), Target::Vim, Some("nnoremap a<Space>b c<Bar>d\nxnoremap a<Space>b c<Bar>d")),
+        case(keymap(&["!"], "jj", command("<Esc>")), Target::Vim, Some("noremap! jj <Esc>")),
+        case(keymap(&["n"], "a", lua("function() end")), Target::Vim, None),
+        case(keymap(&["n"], "a", command("<cmd>Files<cr>")), Target::Neovim, Some(r#"vim.keymap.set({"n"}, "a", "<cmd>Files<cr>", {})"#)),
+        case(keymap(&["n", "x"], "a", lua("function() end")), Target::Neovim, Some(r#"vim.keymap.set({"n","x"}, "a", function() end, {})"#)),
+        case(keymap(&["q"], "a", command("b")), Target::Neovim, None),
+    )]
+    fn test_mk_keymap_code(arg: payload::Keymap, target: Target, exp: Option<&str>) {
+        let act = mk_keymap_code(
+            &[&arg],
+            &target,
+            &Owner::Plugin {
+                id: "foo",
+                package: "/nix/store/foo",
+            },
+        );
+
+        assert_eq!(exp, act.ok().as_deref());
+    }
+
+    #[rstest(
+        target,
+        exp,
+        case(Target::Vim, "nnoremap <buffer> <silent> a b"),
+        case(
+            Target::Neovim,
+            r#"vim.keymap.set({"n"}, "a", "b", {desc="c",silent=true,buffer=true})"#
+        )
+    )]
+    fn test_mk_keymap_code_opts(target: Target, exp: &str) {
+        let arg = payload::Keymap {
+            desc: "c".to_string(),
+            silent: true,
+            buffer: true,
+            ..keymap(&["n"], "a", command("b"))
+        };
+
+        let act = mk_keymap_code(
+            &[&arg],
+            &target,
+            &Owner::Plugin {
+                id: "foo",
+                package: "/nix/store/foo",
+            },
+        )
+        .unwrap();
+
+        assert_eq!(exp, act);
+    }
+}
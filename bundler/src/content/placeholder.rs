@@ -0,0 +1,154 @@
+use crate::content::id_table::IdTable;
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+
+/// where a config is written.
+pub enum Owner<'a> {
+    /// a plugin, named by its id.
+    Plugin { id: &'a str, package: &'a str },
+    /// name of a group.
+    Group(&'a str),
+    /// path of an after file, e.g. `after/ftplugin/nix`.
+    After(String),
+    /// the bundler config itself.
+    Bundler,
+}
+
+impl fmt::Display for Owner<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Owner::Plugin { id, .. } => write!(f, "{}", id),
+            Owner::Group(name) => write!(f, "{}", name),
+            Owner::After(path) => write!(f, "{}", path),
+            Owner::Bundler => write!(f, "bundler"),
+        }
+    }
+}
+
+/// values of `${...}` in config code.
+///
+/// - `${plugin.path}`: nix package of the plugin.
+/// - `${plugin.id}`: plugin id.
+/// - `${bundler.root}`: output directory of the bundle.
+///
+/// `$${...}` is written as `${...}`.
+pub struct Placeholders<'a> {
+    pub id_table: &'a IdTable,
+    /// unknown when the bundle is streamed.
+    pub root_dir: Option<&'a str>,
+}
+
+impl Placeholders<'_> {
+    /// owner of the configs of `package`, which falls back to the package if it has no id.
+    pub fn plugin_owner<'b>(&'b self, package: &'b str) -> Owner<'b> {
+        Owner::Plugin {
+            id: self.id_table.find(package).unwrap_or(package),
+            package,
+        }
+    }
+
+    fn resolve(&self, name: &str, owner: &Owner) -> Result<String> {
+        match (name, owner) {
+            ("plugin.path", Owner::Plugin { package, .. }) => Ok(package.to_string()),
+            ("plugin.id", Owner::Plugin { id, .. }) => Ok(id.to_string()),
+            ("plugin.path" | "plugin.id", _) => bail!(
+                "`${{{}}}` is only available in plugin configs, but used in `{}`.",
+                name,
+                owner
+            ),
+            ("bundler.root", _) => self.root_dir.map(|dir| dir.to_string()).ok_or_else(|| {
+                anyhow!(
+                    "`${{bundler.root}}` of `{}` is unknown when the bundle is streamed.",
+                    owner
+                )
+            }),
+            _ => bail!(
+                "unknown placeholder `${{{}}}` in config of `{}` (write `$${{{}}}` to keep it).",
+                name,
+                owner,
+                name
+            ),
+        }
+    }
+
+    /// replace placeholders in config code of `owner`.
+    pub fn expand(&self, code: &str, owner: &Owner) -> Result<String> {
+        let mut expanded = String::with_capacity(code.len());
+        let mut rest = code;
+        while let Some(start) = rest.find("${") {
+            // escaped
+            if rest[..start].ends_with('$') {
+                expanded.push_str(&rest[..start - 1]);
+                expanded.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            expanded.push_str(&rest[..start]);
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => bail!("unterminated placeholder in config of `{}`.", owner),
+            };
+            expanded.push_str(&self.resolve(&rest[start + 2..end], owner)?);
+            rest = &rest[end + 1..];
+        }
+        expanded.push_str(rest);
+
+        Ok(expanded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::IdMapElement;
+    use rstest::rstest;
+
+    #[rstest(
+        code,
+        root_dir,
+        exp,
+        case("", None, Some("")),
+        case("a $b {c}", None, Some("a $b {c}")),
+        case("${plugin.path}/bin", None, Some("/nix/store/a-foo/bin")),
+        case("require('${plugin.id}')", None, Some("require('foo')")),
+        case("${bundler.root}/x", Some("/out"), Some("/out/x")),
+        case("${bundler.root}", None, None),
+        case("$${plugin.id}", None, Some("${plugin.id}")),
+        case("$$${plugin.id}", None, Some("$${plugin.id}")),
+        case("${plugin.name}", None, None),
+        case("${plugin.id", None, None)
+    )]
+    fn test_expand(code: &str, root_dir: Option<&str>, exp: Option<&str>) {
+        let id_table = IdTable::from(vec![IdMapElement {
+            plugin_id: "foo".to_string(),
+            package: "/nix/store/a-foo".to_string(),
+        }]);
+        let placeholders = Placeholders {
+            id_table: &id_table,
+            root_dir,
+        };
+
+        let act = placeholders.expand(code, &placeholders.plugin_owner("/nix/store/a-foo"));
+
+        assert_eq!(exp, act.ok().as_deref());
+    }
+
+    #[test]
+    fn test_expand_group() {
+        let id_table = IdTable::from(vec![]);
+        let placeholders = Placeholders {
+            id_table: &id_table,
+            root_dir: Some("/out"),
+        };
+
+        assert!(placeholders
+            .expand("${plugin.path}", &Owner::Group("bar"))
+            .is_err());
+        assert_eq!(
+            "/out",
+            placeholders
+                .expand("${bundler.root}", &Owner::Group("bar"))
+                .unwrap()
+        );
+    }
+}
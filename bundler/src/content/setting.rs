@@ -0,0 +1,174 @@
+use crate::content::common::Target;
+use crate::content::placeholder::Owner;
+use crate::payload;
+use crate::util::lua::to_lua_string;
+use crate::util::vim::to_vim_string;
+use anyhow::{bail, Result};
+
+/// option names are words, global names may also contain `#` of autoload.
+fn is_valid_name(name: &str, allow_hash: bool) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || (allow_hash && c == '#'))
+}
+
+/// `.name` if `name` is a lua identifier, `["name"]` otherwise.
+fn lua_field(name: &str) -> String {
+    let is_identifier = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit());
+    if is_identifier {
+        format!(".{}", name)
+    } else {
+        format!("[{}]", to_lua_string(name))
+    }
+}
+
+fn to_lua_value(value: &payload::VimValue) -> String {
+    match value {
+        payload::VimValue::Bool(b) => b.to_string(),
+        payload::VimValue::Number(n) => n.to_string(),
+        payload::VimValue::String(s) => to_lua_string(s),
+        payload::VimValue::List(v) => {
+            let items = v.iter().map(to_lua_value).collect::<Vec<_>>();
+            format!("{{{}}}", items.join(","))
+        }
+    }
+}
+
+fn to_vim_value(value: &payload::VimValue) -> String {
+    match value {
+        payload::VimValue::Bool(b) => format!("v:{}", b),
+        payload::VimValue::Number(n) => n.to_string(),
+        payload::VimValue::String(s) => to_vim_string(s),
+        payload::VimValue::List(v) => {
+            let items = v.iter().map(to_vim_value).collect::<Vec<_>>();
+            format!("[{}]", items.join(","))
+        }
+    }
+}
+
+/// option value of a number or a string.
+fn to_option_value(name: &str, value: &payload::VimValue, owner: &Owner) -> Result<String> {
+    match value {
+        payload::VimValue::Number(n) => Ok(n.to_string()),
+        payload::VimValue::String(s) => Ok(s.to_string()),
+        _ => bail!(
+            "option `{}` of `{}` has a list of other than numbers and strings.",
+            name,
+            owner
+        ),
+    }
+}
+
+/// option value escaped for `:set`.
+fn to_set_value(value: &str) -> String {
+    // white space, `\`, `|` and `"` are escaped with `\`.
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_whitespace() || c == '\\' || c == '|' || c == '"' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+fn mk_vim_option(name: &str, value: &payload::VimValue, owner: &Owner) -> Result<String> {
+    let value = match value {
+        payload::VimValue::Bool(true) => return Ok(format!("set {}", name)),
+        payload::VimValue::Bool(false) => return Ok(format!("set no{}", name)),
+        // comma separated, a comma in an item is `\,`.
+        payload::VimValue::List(v) => v
+            .iter()
+            .map(|item| Ok(to_option_value(name, item, owner)?.replace(',', "\\,")))
+            .collect::<Result<Vec<_>>>()?
+            .join(","),
+        _ => to_option_value(name, value, owner)?,
+    };
+    let value = to_set_value(&value);
+
+    Ok(format!("set {}={}", name, value))
+}
+
+/// code setting `options` and `globals` of `owner` for the target.
+pub fn mk_setting_code(
+    options: &payload::VimValues,
+    globals: &payload::VimValues,
+    target: &Target,
+    owner: &Owner,
+) -> Result<String> {
+    let mut lines = vec![];
+    for (name, value) in options {
+        if !is_valid_name(name, false) {
+            bail!("invalid option name `{}` of `{}`.", name, owner);
+        }
+        lines.push(match target {
+            Target::Vim => mk_vim_option(name, value, owner)?,
+            Target::Neovim => format!("vim.opt{} = {}", lua_field(name), to_lua_value(value)),
+        });
+    }
+    for (name, value) in globals {
+        if !is_valid_name(name, true) {
+            bail!("invalid global variable name `{}` of `{}`.", name, owner);
+        }
+        lines.push(match target {
+            Target::Vim => format!("let g:{} = {}", name, to_vim_value(value)),
+            Target::Neovim => format!("vim.g{} = {}", lua_field(name), to_lua_value(value)),
+        });
+    }
+
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn string(s: &str) -> payload::VimValue {
+        payload::VimValue::String(s.to_string())
+    }
+
+    fn number(n: i64) -> payload::VimValue {
+        payload::VimValue::Number(n.into())
+    }
+
+    fn values(v: Vec<(&str, payload::VimValue)>) -> payload::VimValues {
+        v.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+    }
+
+    #[rstest(options, globals, target, exp,
+        case(vec![], vec![], Target::Vim, Some("")),
+        case(vec![("number", payload::VimValue::Bool(true)), ("wrap", payload::VimValue::Bool(false))], vec![], Target::Vim, Some("set number\nset nowrap")),
+        case(vec![("tabstop", number(2))], vec![], Target::Vim, Some("set tabstop=2")),
+        case(vec![("statusline", string(r#"%f | "a\b""#))], vec![], Target::Vim, Some(r#"set statusline=%f\ \|\ \"a\\b\""#)),
+        case(vec![("wildignore", payload::VimValue::List(vec![string("*.o"), string("*.a")]))], vec![], Target::Vim, Some("set wildignore=*.o,*.a")),
+        case(vec![("wildignore", payload::VimValue::List(vec![string("a,b"), string("c")]))], vec![], Target::Vim, Some(r"set wildignore=a\\,b,c")),
+        case(vec![("wildignore", payload::VimValue::List(vec![payload::VimValue::List(vec![])]))], vec![], Target::Vim, None),
+        case(vec![], vec![("foo#bar", payload::VimValue::List(vec![string("a'b"), payload::VimValue::Bool(true)]))], Target::Vim, Some(r#"let g:foo#bar = ["a'b",v:true]"#)),
+        case(vec![("tabstop", number(2))], vec![("mapleader", string(" "))], Target::Neovim, Some("vim.opt.tabstop = 2\nvim.g.mapleader = \" \"")),
+        case(vec![], vec![("foo#bar", payload::VimValue::List(vec![number(1)]))], Target::Neovim, Some(r#"vim.g["foo#bar"] = {1}"#)),
+        case(vec![("a b", number(1))], vec![], Target::Neovim, None),
+        case(vec![], vec![("a.b", number(1))], Target::Vim, None),
+    )]
+    fn test_mk_setting_code(
+        options: Vec<(&str, payload::VimValue)>,
+        globals: Vec<(&str, payload::VimValue)>,
+        target: Target,
+        exp: Option<&str>,
+    ) {
+        let act = mk_setting_code(
+            &values(options),
+            &values(globals),
+            &target,
+            &Owner::Plugin {
+                id: "foo",
+                package: "/nix/store/foo",
+            },
+        );
+
+        assert_eq!(exp, act.ok().as_deref());
+    }
+}
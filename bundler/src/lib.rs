@@ -0,0 +1,46 @@
+//! Nix friendly vim/neovim plugin bundler.
+//!
+//! the configuration flows through the following stages.
+//!
+//! 1. [`payload::Payload`]: JSON generated in Nix.
+//! 2. [`content::unpack`]: resolve the recursive structure of the payload for a target.
+//! 3. [`bundle::bundle`]: index plugins and their load triggers.
+//! 4. [`bundle::export`]: write the files read by bundler-vim/bundler-nvim
+//!    in the format of a [`bundle::Backend`].
+//!
+//! other formats can be added by implementing [`bundle::Backend`],
+//! or [`bundle::Sink`] to reuse the key layout of the built-in backends.
+//!
+//! the items re-exported by these modules are the public API of the library. it follows
+//! semver, so a breaking change to them only comes with a new major version. everything
+//! else is private to the crate.
+//!
+//! ```no_run
+//! use bundler::{bundle, content, payload::Payload};
+//!
+//! let json = std::fs::read_to_string("payload.json")?;
+//! let payload = serde_json::from_str::<Payload>(&json)?;
+//! let content = content::unpack(payload, Some("/path/to/out"))?;
+//! let bundle = bundle::bundle(&content)?;
+//! bundle::export(
+//!     bundle,
+//!     &bundle::LuaBackend,
+//!     bundle::ExportOption {
+//!         root_dir: "/path/to/out".to_string(),
+//!         prune: true,
+//!     },
+//! )?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+#![warn(missing_docs)]
+
+#[cfg_attr(test, macro_use)]
+extern crate derive_builder;
+
+pub mod bundle;
+pub mod content;
+pub mod payload;
+
+mod constant;
+mod util;
@@ -1,42 +1,86 @@
-#[cfg_attr(test, macro_use)]
-extern crate derive_builder;
-
-mod constant;
-mod content;
-mod bundle;
-mod payload;
-mod util;
-
-use crate::payload::Payload;
+use anyhow::{bail, Context, Result};
+use bundler::bundle::{self, Registry};
+use bundler::{content, payload::Payload};
+use std::io::{self, Read};
+use std::path::Path;
 use std::{env, fs};
 
-fn main() {
+/// `-` stands for stdin (input) or a tar archive on stdout (output).
+const STDIO: &str = "-";
+
+fn main() -> Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
     log::info!("bundle start");
 
-    let args: Vec<String> = env::args().collect();
-    let input_json_path = &args[1];
-    let output_dir = &args[2];
+    // `--keep-stale` keeps files of the previous export that are no longer produced.
+    // `--format <name>` selects the output format.
+    let mut args = vec![];
+    let mut keep_stale = false;
+    let mut format = String::from("lua");
+    let mut argv = env::args();
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--keep-stale" => keep_stale = true,
+            "--format" => format = argv.next().context("`--format` requires a name")?,
+            _ => args.push(arg),
+        }
+    }
+    let (input_json_path, output_dir) = match &args[..] {
+        [_, input, output] => (input, output),
+        _ => {
+            bail!("usage: bundler [--keep-stale] [--format <name>] <payload.json|-> <output dir|->")
+        }
+    };
     log::debug!(
-        "input json: {}, output dir: {}",
+        "input json: {}, output dir: {}, format: {}",
         input_json_path,
-        output_dir
+        output_dir,
+        format
     );
+    let registry = Registry::default();
+    let backend = registry.get(&format)?;
 
-    let input_json_text = fs::read_to_string(input_json_path).unwrap();
+    let input_json_text = if input_json_path == STDIO {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        fs::read_to_string(input_json_path)
+            .with_context(|| format!("failed to read `{}`", input_json_path))?
+    };
 
     // convert JSON generated in Nix to Rust struct.
-    let payload = serde_json::from_str::<Payload>(input_json_text.as_str()).unwrap();
+    let payload = serde_json::from_str::<Payload>(input_json_text.as_str())?;
 
     // resolve the recursive structure of payload.
-    let content = content::unpack(payload);
+    let root_dir = if output_dir == STDIO {
+        None
+    } else {
+        let root_dir = Path::new(output_dir);
+        let root_dir = if root_dir.is_absolute() {
+            root_dir.to_path_buf()
+        } else {
+            env::current_dir()
+                .context("failed to resolve the output dir")?
+                .join(root_dir)
+        };
+        Some(root_dir.to_string_lossy().to_string())
+    };
+    let content = content::unpack(payload, root_dir.as_deref())?;
 
     // generate files for bundler-vim/bundler-nvim.
-    let bundle = bundle::bundle(&content);
-    let export_option = bundle::ExportOption {
-        root_dir: output_dir,
-    };
-    bundle::export(bundle, export_option).unwrap();
+    let bundle = bundle::bundle(&content)?;
+    if output_dir == STDIO {
+        bundle::export_tar(bundle, backend, io::stdout().lock())?;
+    } else {
+        let export_option = bundle::ExportOption {
+            root_dir: output_dir.to_string(),
+            prune: !keep_stale,
+        };
+        bundle::export(bundle, backend, export_option)?;
+    }
 
     log::info!("bundle completed");
+
+    Ok(())
 }
@@ -1,10 +1,16 @@
-/* Nix friendly vim/neovim config. */
-pub use crate::payload::{
-    common::{Language, Target},
-    config::{BundlerConfig, Config, DetailConfig, IdMapElement, Meta, AfterOption, Payload},
-    eager::{PluginConfig as PluginEagerConfig, VimPluginPackage as EagerVimPluginPackage},
+//! Nix friendly vim/neovim config.
+pub use crate::payload::config::Payload;
+#[cfg(test)]
+pub(crate) use crate::payload::config::{CommandStub, Configs, LuaRhs};
+pub(crate) use crate::payload::{
+    common::{Collisions, Language, Target},
+    config::{
+        AfterOption, CommandRange, CommandTrigger, Config, DetailConfig, FileConfig, IdMapElement,
+        Keymap, KeymapRhs, VimValue, VimValues,
+    },
+    eager::VimPluginPackage as EagerVimPluginPackage,
     group::LazyGroup,
-    lazy::{PluginConfig as PluginLazyConfig, VimPluginPackage as LazyVimPluginPackage},
+    lazy::VimPluginPackage as LazyVimPluginPackage,
 };
 
 mod common;
@@ -15,3 +15,12 @@ pub enum Target {
     Vim,
     Neovim,
 }
+
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Collisions {
+    Off,
+    #[default]
+    Warn,
+    Error,
+}
@@ -1,10 +1,10 @@
-use crate::payload::common::{Language, Target};
+use crate::payload::common::{Collisions, Language, Target};
 use crate::payload::eager;
 use crate::payload::group;
 use crate::payload::lazy;
 use serde::Deserialize;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
 #[serde(untagged)]
@@ -13,6 +13,8 @@ pub enum Config {
     Simple(String),
     // Config code with language and args.
     Detail(DetailConfig),
+    // Config code read from a file at build time.
+    File(FileConfig),
 }
 impl Default for Config {
     fn default() -> Self {
@@ -20,6 +22,22 @@ impl Default for Config {
     }
 }
 
+/// one config, or several sourced in order.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Configs {
+    One(Config),
+    Many(Vec<Config>),
+}
+impl Configs {
+    pub fn into_vec(self) -> Vec<Config> {
+        match self {
+            Configs::One(cfg) => vec![cfg],
+            Configs::Many(cfgs) => cfgs,
+        }
+    }
+}
+
 #[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
 #[cfg_attr(test, derive(Builder))]
 #[serde(rename_all = "camelCase")]
@@ -29,6 +47,87 @@ pub struct DetailConfig {
     pub args: Value,
 }
 
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Builder))]
+#[serde(rename_all = "camelCase")]
+pub struct FileConfig {
+    // inferred from the extension of `file` when null.
+    pub language: Option<Language>,
+    pub file: String,
+    pub args: Value,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum VimValue {
+    Bool(bool),
+    Number(serde_json::Number),
+    String(String),
+    List(Vec<VimValue>),
+}
+
+// name -> value.
+pub type VimValues = BTreeMap<String, VimValue>;
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum CommandTrigger {
+    // Loaded on `CmdUndefined`.
+    Name(String),
+    // Loaded by a stub command of the same name.
+    Stub(CommandStub),
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum CommandRange {
+    // `-range` if true.
+    Enabled(bool),
+    // `-range=N`.
+    Count(u32),
+    // `-range=%`.
+    Whole(String),
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandStub {
+    pub name: String,
+    pub nargs: String,
+    pub range: CommandRange,
+    pub bang: bool,
+    // `-complete`, none if empty.
+    pub complete: String,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum KeymapRhs {
+    // Keys, e.g. `<cmd>Telescope<cr>`.
+    Command(String),
+    // Lua expression, e.g. `function() ... end`.
+    Lua(LuaRhs),
+}
+
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Builder))]
+#[serde(rename_all = "camelCase")]
+pub struct LuaRhs {
+    pub lua: String,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Keymap {
+    // short mode names, e.g. `n`, `x` or `!`.
+    pub mode: Vec<String>,
+    pub lhs: String,
+    pub rhs: KeymapRhs,
+    pub desc: String,
+    pub silent: bool,
+    pub buffer: bool,
+}
+
 #[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
 #[cfg_attr(test, derive(Builder))]
 #[serde(rename_all = "camelCase")]
@@ -51,7 +150,13 @@ pub struct Meta {
 #[cfg_attr(test, derive(Builder))]
 #[serde(rename_all = "camelCase")]
 pub struct AfterOption {
-    pub ftplugin: HashMap<String, String>,
+    pub ftplugin: HashMap<String, Configs>,
+    pub plugin: HashMap<String, Config>,
+    pub syntax: HashMap<String, Config>,
+    pub indent: HashMap<String, Config>,
+    pub lsp: HashMap<String, Config>,
+    // language -> query name -> query.
+    pub queries: HashMap<String, HashMap<String, String>>,
 }
 
 #[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
@@ -63,12 +168,44 @@ pub struct BundlerConfig {
     pub lazy_groups: Vec<group::LazyGroup>,
     pub package: String,
     pub after: AfterOption,
+    // `vim.opt`, set at startup.
+    pub options: VimValues,
+    // `vim.g`, set at startup.
+    pub globals: VimValues,
+    pub prefer_eager: bool,
+    pub auto_triggers: bool,
+    pub collisions: Collisions,
+    pub flatten_eager: bool,
 }
 
+/// JSON generated in Nix.
 #[derive(Debug, Default, Deserialize, PartialEq, Eq)]
 #[cfg_attr(test, derive(Builder))]
 #[serde(rename_all = "camelCase")]
 pub struct Payload {
+    /// the options of the nix module.
     pub config: BundlerConfig,
+    /// what nix resolved for the options, e.g. plugin ids.
     pub meta: Meta,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(json, exp,
+        case(r#"{"nix": "a"}"#, vec![Config::Simple("a".to_string())]),
+        case(r#"{"nix": ["a", "b"]}"#, vec![Config::Simple("a".to_string()), Config::Simple("b".to_string())]),
+        case(r#"{"nix": {"language": "lua", "code": "a", "args": null}}"#, vec![Config::Detail(DetailConfig {
+            language: Language::Lua,
+            code: "a".to_string(),
+            args: Value::Null,
+        })]),
+    )]
+    fn test_deserialize_ftplugin(json: &str, exp: Vec<Config>) {
+        let act = serde_json::from_str::<HashMap<String, Configs>>(json).unwrap();
+
+        assert_eq!(exp, act["nix"].clone().into_vec());
+    }
+}
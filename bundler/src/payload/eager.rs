@@ -1,11 +1,11 @@
-use crate::payload::config::Config;
+use crate::payload::config::{Config, Keymap, VimValues};
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum VimPluginPackage {
     SimplePackage(String),
-    ConfiguredPackage(PluginConfig),
+    ConfiguredPackage(Box<PluginConfig>),
 }
 
 #[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
@@ -14,5 +14,8 @@ pub enum VimPluginPackage {
 pub struct PluginConfig {
     pub plugin: String,
     pub startup_config: Config,
+    pub keymaps: Vec<Keymap>,
+    pub options: VimValues,
+    pub globals: VimValues,
     pub extra_packages: Vec<String>,
 }
@@ -1,4 +1,7 @@
-use crate::payload::{config::Config, lazy};
+use crate::payload::{
+    config::{CommandTrigger, Config, Keymap},
+    lazy,
+};
 use serde::Deserialize;
 
 #[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
@@ -11,11 +14,19 @@ pub struct LazyGroup {
     pub extra_packages: Vec<String>,
     pub pre_config: Config,
     pub post_config: Config,
+    pub keymaps: Vec<Keymap>,
     pub depend_plugins: Vec<lazy::VimPluginPackage>,
     pub depend_groups: Vec<String>,
     pub on_modules: Vec<String>,
     pub on_events: Vec<String>,
     pub on_filetypes: Vec<String>,
-    pub on_commands: Vec<String>,
+    pub on_commands: Vec<CommandTrigger>,
+    pub on_functions: Vec<String>,
+    pub on_sources: Vec<String>,
+    /// alias of `on_sources`.
+    pub on_plugins: Vec<String>,
+    pub on_colorschemes: Vec<String>,
+    pub on_root_markers: Vec<String>,
     pub use_timer: bool,
+    pub use_denops: bool,
 }
@@ -1,11 +1,11 @@
-use crate::payload::config::Config;
+use crate::payload::config::{CommandTrigger, Config, Keymap, VimValues};
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum VimPluginPackage {
     SimplePackage(String),
-    ConfiguredPackage(PluginConfig),
+    ConfiguredPackage(Box<PluginConfig>),
 }
 
 #[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
@@ -16,12 +16,21 @@ pub struct PluginConfig {
     pub startup_config: Config,
     pub pre_config: Config,
     pub post_config: Config,
+    pub keymaps: Vec<Keymap>,
+    pub options: VimValues,
+    pub globals: VimValues,
     pub depend_plugins: Vec<VimPluginPackage>,
     pub depend_groups: Vec<String>,
     pub on_modules: Vec<String>,
     pub on_events: Vec<String>,
     pub on_filetypes: Vec<String>,
-    pub on_commands: Vec<String>,
+    pub on_commands: Vec<CommandTrigger>,
+    pub on_functions: Vec<String>,
+    pub on_sources: Vec<String>,
+    /// alias of `on_sources`.
+    pub on_plugins: Vec<String>,
+    pub on_colorschemes: Vec<String>,
+    pub on_root_markers: Vec<String>,
     pub use_timer: bool,
     pub use_denops: bool,
 }
@@ -1,2 +1,6 @@
 pub mod file;
 pub mod lua;
+pub mod tar;
+#[cfg(test)]
+pub mod temp;
+pub mod vim;
@@ -1,8 +1,10 @@
+use anyhow::{bail, Context, Result};
 use std::{
+    collections::BTreeSet,
     fs::{self, File},
-    path::Path,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
 };
-use anyhow::{Result, Context};
 
 pub fn create_file_with_dirs<P: AsRef<Path>>(path: P) -> Result<File> {
     if let Some(parent_dir) = path.as_ref().parent() {
@@ -10,3 +12,229 @@ pub fn create_file_with_dirs<P: AsRef<Path>>(path: P) -> Result<File> {
     }
     File::create(path).context("failed to create file")
 }
+
+/// entries of `dir` sorted by name, empty if `dir` does not exist.
+pub fn read_dir_entries(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut entries = vec![];
+    for entry in fs::read_dir(dir).with_context(|| format!("failed to read `{}`", dir.display()))? {
+        let path = entry?.path();
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            entries.push((name.to_string(), path.clone()));
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+/// files under `dir` recursively, sorted by path.
+pub fn read_files_recursive(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for (_, path) in read_dir_entries(dir)? {
+        if path.is_dir() {
+            files.extend(read_files_recursive(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// counts of files touched by an export.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncStats {
+    /// new or changed.
+    pub written: usize,
+    /// kept from the previous export.
+    pub unchanged: usize,
+    /// dropped from the previous export.
+    pub deleted: usize,
+}
+
+/// files and symlinks under `dir` relative to it, without following symlinks.
+fn read_entries_relative(dir: &Path, relative: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = vec![];
+    for (name, path) in read_dir_entries(&dir.join(relative))? {
+        let relative = relative.join(name);
+        if fs::symlink_metadata(&path)?.is_dir() {
+            entries.extend(read_entries_relative(dir, &relative)?);
+        } else {
+            entries.push(relative);
+        }
+    }
+    Ok(entries)
+}
+
+/// files and symlinks under `dir`, without following symlinks.
+pub fn read_entries(dir: &Path) -> Result<Vec<PathBuf>> {
+    read_entries_relative(dir, Path::new(""))
+}
+
+fn is_same_entry(a: &Path, b: &Path) -> Result<bool> {
+    let (a_meta, b_meta) = match (fs::symlink_metadata(a), fs::symlink_metadata(b)) {
+        (Ok(a_meta), Ok(b_meta)) => (a_meta, b_meta),
+        _ => return Ok(false),
+    };
+    if a_meta.is_symlink() || b_meta.is_symlink() {
+        return Ok(a_meta.is_symlink()
+            && b_meta.is_symlink()
+            && fs::read_link(a)? == fs::read_link(b)?);
+    }
+    Ok(a_meta.is_file()
+        && b_meta.is_file()
+        && a_meta.len() == b_meta.len()
+        && fs::read(a)? == fs::read(b)?)
+}
+
+/// hard link a file, or copy a symlink, from `from` to `to`.
+fn link_entry(from: &Path, to: &Path) -> Result<()> {
+    if to.exists() || to.is_symlink() {
+        fs::remove_file(to)?;
+    }
+    if let Some(parent_dir) = to.parent() {
+        fs::create_dir_all(parent_dir)?;
+    }
+    if fs::symlink_metadata(from)?.is_symlink() {
+        symlink(fs::read_link(from)?, to)?;
+    } else {
+        fs::hard_link(from, to).with_context(|| format!("failed to link `{}`", to.display()))?;
+    }
+    Ok(())
+}
+
+/// reuse files of `dest` in the freshly built `staging`, so unchanged files keep their
+/// inode and mtime after swapping the directories.
+///
+/// files only in `dest` are deleted if `prune`, otherwise carried over.
+pub fn reuse_unchanged(staging: &Path, dest: &Path, prune: bool) -> Result<SyncStats> {
+    let mut stats = SyncStats::default();
+    let staging_entries = read_entries(staging)?.into_iter().collect::<BTreeSet<_>>();
+
+    for relative in &staging_entries {
+        let (new, old) = (staging.join(relative), dest.join(relative));
+        if is_same_entry(&new, &old)? {
+            link_entry(&old, &new)?;
+            stats.unchanged += 1;
+        } else {
+            stats.written += 1;
+        }
+    }
+
+    for relative in read_entries(dest)? {
+        if staging_entries.contains(&relative) {
+            continue;
+        }
+        if prune {
+            stats.deleted += 1;
+        } else {
+            link_entry(&dest.join(&relative), &staging.join(&relative))?;
+            stats.unchanged += 1;
+        }
+    }
+
+    Ok(stats)
+}
+
+/// point the symlink `link` at `target`, a sibling directory of it.
+///
+/// the new link is renamed over the old one, so readers see either tree but never a
+/// missing one. a plain directory at `link` (e.g. from an older export) can not be
+/// replaced by a rename, it is moved aside first and `link` is briefly missing.
+pub fn swap_link(target: &Path, link: &Path) -> Result<()> {
+    let (name, target_name) = match (link.file_name(), target.file_name()) {
+        (Some(name), Some(target_name)) => (name, target_name),
+        _ => bail!("invalid link `{}`.", link.display()),
+    };
+    let tmp_link = link.with_file_name(format!(".{}.link", name.to_string_lossy()));
+    if tmp_link.is_symlink() {
+        fs::remove_file(&tmp_link)?;
+    }
+    symlink(target_name, &tmp_link)?;
+
+    let is_dir = fs::symlink_metadata(link).is_ok_and(|meta| meta.is_dir());
+    if is_dir {
+        let old_dir = link.with_file_name(format!(".{}.old", name.to_string_lossy()));
+        if old_dir.exists() {
+            fs::remove_dir_all(&old_dir)?;
+        }
+        fs::rename(link, &old_dir)?;
+        fs::rename(&tmp_link, link)?;
+        fs::remove_dir_all(&old_dir)?;
+    } else {
+        fs::rename(&tmp_link, link)
+            .with_context(|| format!("failed to replace `{}`", link.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp::TempDir;
+    use rstest::rstest;
+    use std::os::unix::fs::MetadataExt;
+
+    #[rstest(
+        prune,
+        exp_stale,
+        exp_deleted,
+        case(true, false, 1),
+        case(false, true, 0)
+    )]
+    fn test_reuse_unchanged(prune: bool, exp_stale: bool, exp_deleted: usize) {
+        let root = TempDir::new("reuse");
+        let (staging, dest) = (root.join("staging"), root.join("dest"));
+        root.write("staging/same", "same");
+        root.write("staging/changed", "new");
+        root.write("staging/dir/added", "added");
+        symlink("/nix/store/foo", staging.join("link")).unwrap();
+        root.write("dest/same", "same");
+        root.write("dest/changed", "old");
+        root.write("dest/stale/file", "stale");
+        symlink("/nix/store/foo", dest.join("link")).unwrap();
+
+        let act = reuse_unchanged(&staging, &dest, prune).unwrap();
+        let same_ino = fs::metadata(staging.join("same")).unwrap().ino();
+        let old_ino = fs::metadata(dest.join("same")).unwrap().ino();
+        let changed = fs::read_to_string(staging.join("changed")).unwrap();
+        let added = fs::read_to_string(staging.join("dir/added")).unwrap();
+        let stale = staging.join("stale/file").exists();
+
+        assert_eq!(
+            SyncStats {
+                written: 2,
+                unchanged: 3 - exp_deleted,
+                deleted: exp_deleted
+            },
+            act
+        );
+        assert_eq!(old_ino, same_ino);
+        assert_eq!("new", changed);
+        assert_eq!("added", added);
+        assert_eq!(exp_stale, stale);
+    }
+
+    #[rstest(is_dir, case(true), case(false))]
+    fn test_swap_link(is_dir: bool) {
+        let root = TempDir::new("swap");
+        let link = root.join("out");
+        root.write(".out.new/file", "new");
+        if is_dir {
+            root.write("out/file", "old");
+        } else {
+            root.write(".out.prev/file", "old");
+            symlink(".out.prev", &link).unwrap();
+        }
+
+        swap_link(&root.join(".out.new"), &link).unwrap();
+        let target = fs::read_link(&link).unwrap();
+        let file = fs::read_to_string(link.join("file")).unwrap();
+        let leftovers = [".out.link", ".out.old"].map(|name| root.join(name).exists());
+
+        assert_eq!(PathBuf::from(".out.new"), target);
+        assert_eq!("new", file);
+        assert_eq!([false, false], leftovers);
+    }
+}
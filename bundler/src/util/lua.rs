@@ -1,17 +1,11 @@
-/// rust list to lua table (vector).
-pub fn to_lua_table(v: &[&str]) -> String {
-    let wrap = v.iter().map(|s| format!("\"{}\"", s)).collect::<Vec<_>>();
-    ["{", wrap.join(",").as_ref(), "}"].join("")
-}
-
-/// rust list to lua table (dictionary).
-pub fn to_lua_flag_table(v: &[&str], default: bool) -> String {
-    let default = if default { "true" } else { "false" };
-    let wrap = v
-        .iter()
-        .map(|s| format!("[\"{}\"]={}", s, default))
-        .collect::<Vec<_>>();
-    ["{", wrap.join(",").as_ref(), "}"].join("")
+/// rust str to lua string literal.
+pub fn to_lua_string(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\"{}\"", escaped)
 }
 
 #[cfg(test)]
@@ -19,27 +13,16 @@ mod tests {
     use super::*;
     use rstest::rstest;
 
-    #[rstest(arg, exp,
-        case(vec![], r#"{}"#),
-        case(vec!["a"], r#"{"a"}"#),
-        case(vec!["a","b"], r#"{"a","b"}"#),
-    )]
-    fn test_to_lua_table(arg: Vec<&str>, exp: String) {
-        let act = to_lua_table(&arg);
-
-        assert_eq!(exp, act);
-    }
-
-    #[rstest(arg, default, exp,
-        case(vec![], true, r#"{}"#),
-        case(vec![], false, r#"{}"#),
-        case(vec!["a"],true, r#"{["a"]=true}"#),
-        case(vec!["a"],false, r#"{["a"]=false}"#),
-        case(vec!["a","b"],true, r#"{["a"]=true,["b"]=true}"#),
-        case(vec!["a","b"],false, r#"{["a"]=false,["b"]=false}"#),
+    #[rstest(
+        arg,
+        exp,
+        case("", r#""""#),
+        case("a", r#""a""#),
+        case(r#"a"b\c"#, r#""a\"b\\c""#),
+        case("a\nb", r#""a\nb""#)
     )]
-    fn test_to_lua_flag_table(arg: Vec<&str>, default: bool, exp: String) {
-        let act = to_lua_flag_table(&arg, default);
+    fn test_to_lua_string(arg: &str, exp: String) {
+        let act = to_lua_string(arg);
 
         assert_eq!(exp, act);
     }
@@ -0,0 +1,168 @@
+use anyhow::{bail, Result};
+use std::io::Write;
+
+const BLOCK_SIZE: usize = 512;
+
+/// a minimal ustar writer with pax headers for long names.
+///
+/// entries have a fixed owner and mtime so the archive is reproducible.
+pub struct TarWriter<W: Write> {
+    inner: W,
+}
+
+fn write_octal(field: &mut [u8], value: u64) -> Result<()> {
+    let digits = format!("{:o}", value);
+    let end = field.len() - 1;
+    if digits.len() > end {
+        bail!("`{}` does not fit in a tar header field.", value);
+    }
+    let start = end - digits.len();
+    field[..start].fill(b'0');
+    field[start..end].copy_from_slice(digits.as_bytes());
+    field[end] = 0;
+    Ok(())
+}
+
+fn write_str(field: &mut [u8], value: &str) {
+    let bytes = value.as_bytes();
+    let len = bytes.len().min(field.len());
+    field[..len].copy_from_slice(&bytes[..len]);
+}
+
+/// `<len> <key>=<value>\n`, where `<len>` counts the whole record.
+fn pax_record(key: &str, value: &str) -> String {
+    let body = format!(" {}={}\n", key, value);
+    let mut len = body.len() + 1;
+    while (len.to_string().len() + body.len()) != len {
+        len = len.to_string().len() + body.len();
+    }
+    format!("{}{}", len, body)
+}
+
+impl<W: Write> TarWriter<W> {
+    pub fn new(inner: W) -> Self {
+        TarWriter { inner }
+    }
+
+    fn write_header(
+        &mut self,
+        path: &str,
+        mode: u64,
+        size: u64,
+        typeflag: u8,
+        link: &str,
+    ) -> Result<()> {
+        let mut header = [0u8; BLOCK_SIZE];
+        write_str(&mut header[0..100], path);
+        write_octal(&mut header[100..108], mode)?;
+        write_octal(&mut header[108..116], 0)?;
+        write_octal(&mut header[116..124], 0)?;
+        write_octal(&mut header[124..136], size)?;
+        write_octal(&mut header[136..148], 0)?;
+        header[156] = typeflag;
+        write_str(&mut header[157..257], link);
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // the checksum is computed with the checksum field filled with spaces.
+        header[148..156].fill(b' ');
+        let checksum = header.iter().map(|b| *b as u64).sum::<u64>();
+        write_octal(&mut header[148..155], checksum)?;
+
+        self.inner.write_all(&header)?;
+        Ok(())
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<()> {
+        self.inner.write_all(data)?;
+        let padding = (BLOCK_SIZE - data.len() % BLOCK_SIZE) % BLOCK_SIZE;
+        self.inner.write_all(&vec![0u8; padding])?;
+        Ok(())
+    }
+
+    fn append(
+        &mut self,
+        path: &str,
+        mode: u64,
+        typeflag: u8,
+        link: &str,
+        data: &[u8],
+    ) -> Result<()> {
+        let mut records = String::new();
+        if path.len() > 100 {
+            records += &pax_record("path", path);
+        }
+        if link.len() > 100 {
+            records += &pax_record("linkpath", link);
+        }
+        if !records.is_empty() {
+            self.write_header("././@PaxHeader", 0o644, records.len() as u64, b'x', "")?;
+            self.write_data(records.as_bytes())?;
+        }
+        self.write_header(path, mode, data.len() as u64, typeflag, link)?;
+        self.write_data(data)
+    }
+
+    pub fn append_dir(&mut self, path: &str) -> Result<()> {
+        self.append(&format!("{}/", path), 0o755, b'5', "", &[])
+    }
+
+    pub fn append_file(&mut self, path: &str, data: &[u8]) -> Result<()> {
+        self.append(path, 0o644, b'0', "", data)
+    }
+
+    pub fn append_symlink(&mut self, path: &str, target: &str) -> Result<()> {
+        self.append(path, 0o777, b'2', target, &[])
+    }
+
+    /// write the end-of-archive marker.
+    pub fn finish(mut self) -> Result<W> {
+        self.inner.write_all(&[0u8; BLOCK_SIZE * 2])?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(
+        key,
+        value,
+        exp,
+        case("path", "a", "9 path=a\n"),
+        case("path", "abc", "12 path=abc\n"),
+        case("path", &"a".repeat(100), &format!("110 path={}\n", "a".repeat(100)))
+    )]
+    fn test_pax_record(key: &str, value: &str, exp: &str) {
+        let act = pax_record(key, value);
+
+        assert_eq!(exp, act);
+    }
+
+    #[test]
+    fn test_tar_writer() {
+        let mut tar = TarWriter::new(vec![]);
+        tar.append_file("foo/bar", b"baz").unwrap();
+        tar.append_symlink(&"a".repeat(120), "/nix/store/foo")
+            .unwrap();
+        let act = tar.finish().unwrap();
+
+        let header = &act[..BLOCK_SIZE];
+        let checksum = header[..148]
+            .iter()
+            .chain([b' '; 8].iter())
+            .chain(header[156..].iter())
+            .map(|b| *b as u64)
+            .sum::<u64>();
+        assert_eq!(BLOCK_SIZE * 7, act.len());
+        assert_eq!(b"foo/bar\0", &header[..8]);
+        assert_eq!(b"00000000003\0", &header[124..136]);
+        assert_eq!(format!("{:06o}\0", checksum).as_bytes(), &header[148..155]);
+        assert_eq!(b"baz\0", &act[BLOCK_SIZE..BLOCK_SIZE + 4]);
+        assert_eq!(b'x', act[BLOCK_SIZE * 2 + 156]);
+        assert_eq!(b'2', act[BLOCK_SIZE * 4 + 156]);
+    }
+}
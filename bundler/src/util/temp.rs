@@ -0,0 +1,50 @@
+use crate::util::file::create_file_with_dirs;
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// directory for a test, removed when dropped even if the test panics.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// unique per process and call, so tests can run in parallel.
+    pub fn new(name: &str) -> Self {
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("bundler-{}-{}-{}", name, process::id(), count));
+        if path.exists() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.path.join(path)
+    }
+
+    /// write `content` to `path` under the directory, creating parent directories.
+    pub fn write<P: AsRef<Path>>(&self, path: P, content: &str) -> PathBuf {
+        let path = self.path.join(path);
+        let mut file = create_file_with_dirs(&path).unwrap();
+        write!(file, "{}", content).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
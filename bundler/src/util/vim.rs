@@ -0,0 +1,29 @@
+/// rust str to vim string literal.
+pub fn to_vim_string(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(
+        arg,
+        exp,
+        case("", r#""""#),
+        case("a", r#""a""#),
+        case(r#"a"b\c"#, r#""a\"b\\c""#),
+        case("a\nb", r#""a\nb""#)
+    )]
+    fn test_to_vim_string(arg: &str, exp: String) {
+        let act = to_vim_string(arg);
+
+        assert_eq!(exp, act);
+    }
+}
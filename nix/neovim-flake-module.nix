@@ -6,6 +6,88 @@ in {
     perSystem = mkPerSystemOption ({ pkgs, ... }:
       let
         inherit (lib) types mkEnableOption mkOption;
+        pluginConfigFile = types.addCheck (types.submodule {
+          options = {
+            language = mkOption {
+              type = with types; nullOr (enum [ "vim" "lua" ]);
+              description = "inferred from the extension of file when null";
+              default = null;
+            };
+            file = mkOption {
+              type = types.path;
+              description = "file read at build time";
+            };
+            args = mkOption {
+              type = types.attrs;
+              default = { };
+            };
+          };
+        }) (v: v ? file);
+        vimValues = with types;
+          attrsOf (oneOf [ bool int float str (listOf (oneOf [ bool int float str ])) ]);
+        keymap = types.submodule {
+          options = {
+            mode = mkOption {
+              type = with types; coercedTo str lib.toList (listOf str);
+              default = [ "n" ];
+            };
+            lhs = mkOption { type = types.str; };
+            rhs = mkOption {
+              type = with types;
+                either str (submodule {
+                  options.lua = mkOption {
+                    type = types.lines;
+                    description = "lua expression, e.g. a function";
+                  };
+                });
+              description = "keys, or { lua = ...; } for a lua expression";
+            };
+            desc = mkOption {
+              type = types.str;
+              default = "";
+            };
+            silent = mkEnableOption "silent";
+            buffer = mkEnableOption "buffer" // {
+              description = "buffer-local, defined once the plugin is loaded";
+            };
+          };
+        };
+        commandTrigger = types.submodule {
+          options = {
+            name = mkOption { type = types.str; };
+            nargs = mkOption {
+              type = types.enum [ "0" "1" "*" "?" "+" ];
+              default = "*";
+            };
+            range = mkOption {
+              type = with types; oneOf [ bool int (enum [ "%" ]) ];
+              description = "true for a line range, \"%\" for the whole file, a number for a count";
+              default = false;
+            };
+            bang = mkEnableOption "bang";
+            complete = mkOption {
+              type = types.str;
+              description = "completion of the arguments, e.g. \"file\"";
+              default = "";
+            };
+          };
+        };
+        pluginConfigDetail = types.submodule {
+          options = {
+            language = mkOption {
+              type = types.enum [ "vim" "lua" ];
+              default = "vim";
+            };
+            code = mkOption {
+              type = types.lines;
+              default = "";
+            };
+            args = mkOption {
+              type = types.attrs;
+              default = { };
+            };
+          };
+        };
         neovim = {
           target = mkOption {
             type = types.enum [ "neovim" ];
@@ -36,17 +118,64 @@ in {
           };
           after = {
             ftplugin = mkOption {
-              type = with types; attrsOf lines;
-              description = "after/ftplugin configuration (viml)";
+              type = with types;
+                let ftConfig = either lines (either pluginConfigFile pluginConfigDetail);
+                in attrsOf (coercedTo ftConfig lib.toList (listOf ftConfig));
+              description = "after/ftplugin configuration";
               example = ''
                 {
                   lua = "setlocal expandtab";
                   nix = builtins.readFile ./path/to/after/ftplugin/nix.vim;
+                  rust = [
+                    "setlocal shiftwidth=4"
+                    {
+                      language = "lua";
+                      code = "vim.keymap.set('n', '<leader>r', '<cmd>RustRun<cr>', { buffer = true })";
+                    }
+                  ];
                   # ...
                 }
               '';
               default = { };
             };
+            plugin = mkOption {
+              type = with types; attrsOf (either lines (either pluginConfigFile pluginConfigDetail));
+              description = "after/plugin configuration";
+              default = { };
+            };
+            syntax = mkOption {
+              type = with types; attrsOf (either lines (either pluginConfigFile pluginConfigDetail));
+              description = "after/syntax configuration";
+              default = { };
+            };
+            indent = mkOption {
+              type = with types; attrsOf (either lines (either pluginConfigFile pluginConfigDetail));
+              description = "after/indent configuration";
+              default = { };
+            };
+            lsp = mkOption {
+              type = with types; attrsOf (either lines (either pluginConfigFile pluginConfigDetail));
+              description = "after/lsp configuration";
+              example = ''
+                {
+                  lua_ls = {
+                    language = "lua";
+                    code = "return { settings = { Lua = { telemetry = { enable = false } } } }";
+                  };
+                }
+              '';
+              default = { };
+            };
+            queries = mkOption {
+              type = with types; attrsOf (attrsOf lines);
+              description = "after/queries/<lang>/<name>.scm (tree-sitter queries)";
+              example = ''
+                {
+                  nix.injections = builtins.readFile ./path/to/after/queries/nix/injections.scm;
+                }
+              '';
+              default = { };
+            };
           };
           withNodeJs = mkEnableOption "withNodeJs" // {
             description = "Alias for neovim.withNodeJs";
@@ -59,27 +188,11 @@ in {
           };
         };
         bundlerPlugin = let
-          pluginConfigDetail = types.submodule {
-            options = {
-              language = mkOption {
-                type = types.enum [ "vim" "lua" ];
-                default = "vim";
-              };
-              code = mkOption {
-                type = types.lines;
-                default = "";
-              };
-              args = mkOption {
-                type = types.attrs;
-                default = { };
-              };
-            };
-          };
           eagerPluginConfig = types.submodule {
             options = {
               plugin = mkOption { type = types.package; };
               startupConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 description = "Configuration to add before plugin is loaded";
                 default = "";
               };
@@ -88,13 +201,28 @@ in {
                 description = "Extra packages to install";
                 default = [ ];
               };
+              keymaps = mkOption {
+                type = types.listOf keymap;
+                description = "Keymaps to define for the plugin";
+                default = [ ];
+              };
+              options = mkOption {
+                type = vimValues;
+                description = "Options to set at startup";
+                default = { };
+              };
+              globals = mkOption {
+                type = vimValues;
+                description = "Global variables to set at startup";
+                default = { };
+              };
             };
           };
           lazyPluginConfig = types.submodule {
             options = {
               plugin = mkOption { type = types.package; };
               startupConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 description = "Configuration to add before plugin is loaded";
                 default = "";
               };
@@ -103,13 +231,28 @@ in {
                 description = "Extra packages to install";
                 default = [ ];
               };
+              keymaps = mkOption {
+                type = types.listOf keymap;
+                description = "Keymaps to define for the plugin";
+                default = [ ];
+              };
+              options = mkOption {
+                type = vimValues;
+                description = "Options to set at startup";
+                default = { };
+              };
+              globals = mkOption {
+                type = vimValues;
+                description = "Global variables to set at startup";
+                default = { };
+              };
               preConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 description = "Configuration to add before plugin is loaded";
                 default = "";
               };
               postConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 description = "Configuration to add after plugin is loaded";
                 default = "";
               };
@@ -136,6 +279,29 @@ in {
                 default = [ ];
               };
               onCommands = mkOption {
+                type = with types; listOf (either str commandTrigger);
+                default = [ ];
+              };
+              onFunctions = mkOption {
+                type = with types; listOf str;
+                description = "glob patterns of autoload functions, e.g. \"fzf#*\"";
+                default = [ ];
+              };
+              onSources = mkOption {
+                type = with types; listOf package;
+                description = "Plugins to load this plugin after";
+                default = [ ];
+              };
+              onPlugins = mkOption {
+                type = with types; listOf package;
+                description = "alias of onSources";
+                default = [ ];
+              };
+              onColorschemes = mkOption {
+                type = with types; listOf str;
+                default = [ ];
+              };
+              onRootMarkers = mkOption {
                 type = with types; listOf str;
                 default = [ ];
               };
@@ -154,7 +320,7 @@ in {
                 default = [ ];
               };
               startupConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 description = "Configuration to add before plugin is loaded";
                 default = "";
               };
@@ -163,13 +329,18 @@ in {
                 description = "Extra packages to install";
                 default = [ ];
               };
+              keymaps = mkOption {
+                type = types.listOf keymap;
+                description = "Keymaps to define for the plugin";
+                default = [ ];
+              };
               preConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 description = "Configuration to add before plugin is loaded";
                 default = "";
               };
               postConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 description = "Configuration to add after plugin is loaded";
                 default = "";
               };
@@ -196,10 +367,34 @@ in {
                 default = [ ];
               };
               onCommands = mkOption {
+                type = with types; listOf (either str commandTrigger);
+                default = [ ];
+              };
+              onFunctions = mkOption {
+                type = with types; listOf str;
+                description = "glob patterns of autoload functions, e.g. \"fzf#*\"";
+                default = [ ];
+              };
+              onSources = mkOption {
+                type = with types; listOf package;
+                description = "Plugins to load this plugin after";
+                default = [ ];
+              };
+              onPlugins = mkOption {
+                type = with types; listOf package;
+                description = "alias of onSources";
+                default = [ ];
+              };
+              onColorschemes = mkOption {
+                type = with types; listOf str;
+                default = [ ];
+              };
+              onRootMarkers = mkOption {
                 type = with types; listOf str;
                 default = [ ];
               };
               useTimer = mkEnableOption "useTimer";
+              useDenops = mkEnableOption "useDenops";
             };
           };
         in {
@@ -218,6 +413,34 @@ in {
             description = "Plugin groups to install and load on demand";
             default = [ ];
           };
+          preferEager = mkEnableOption "preferEager" // {
+            description =
+              "Load plugins declared both eager and lazy eagerly instead of failing the build";
+          };
+          autoTriggers = mkEnableOption "autoTriggers" // {
+            description =
+              "Infer onModules, onCommands and onFiletypes of lazy plugins from their files";
+          };
+          collisions = mkOption {
+            type = types.enum [ "off" "warn" "error" ];
+            description =
+              "Scan plugins for runtime files that shadow each other, and warn or fail the build";
+            default = "warn";
+          };
+          flattenEager = mkEnableOption "flattenEager" // {
+            description =
+              "Merge eager plugins into a single runtimepath entry of symlinks";
+          };
+          options = mkOption {
+            type = vimValues;
+            description = "Options to set at startup";
+            default = { };
+          };
+          globals = mkOption {
+            type = vimValues;
+            description = "Global variables to set at startup";
+            default = { };
+          };
           timer = mkOption {
             type = types.int;
             description =
@@ -257,7 +480,7 @@ in {
         inherit (lib)
           mapAttrs' nameValuePair flatten optionalString makeBinPath
           escapeShellArgs;
-        inherit (lib.lists) unique;
+        inherit (lib.lists) unique subtractLists;
         inherit (pkgs) writeText;
         inherit (pkgs.stdenv) mkDerivation;
 
@@ -294,9 +517,11 @@ in {
               ++ unique (flatten (map extractVimPlugins cfg.eagerPlugins));
             lazyVimPluginPackages =
               let plugins = with cfg; lazyPlugins ++ lazyGroups;
-              in unique (flatten (map extractVimPlugins plugins));
-            normalizedStartVimPluginPackages =
-              map (p: { plugin = p; }) eagerVimPluginPackages;
+              in subtractLists eagerVimPluginPackages
+              (unique (flatten (map extractVimPlugins plugins)));
+            # eager plugins are linked into `cfgFiles/eager` when flattened.
+            normalizedStartVimPluginPackages = map (p: { plugin = p; })
+              (if cfg.flattenEager then [ bundler-nvim ] else eagerVimPluginPackages);
             normalizedOptVimPluginPackages = map (p: {
               plugin = p;
               optional = true;
@@ -340,6 +565,12 @@ in {
                 " ${name}
                 ${cfg.extraConfig}
                 lua << EOF
+                ${optionalString cfg.flattenEager ''
+                  local rtp = vim.opt.runtimepath:get()
+                  table.insert(rtp, 2, "${cfgFiles}/eager")
+                  table.insert(rtp, "${cfgFiles}/eager/after")
+                  vim.opt.runtimepath = rtp
+                ''}
                 ${cfg.extraLuaConfig}
                 vim.opt.runtimepath:append("${cfgFiles}/after");
                 require("bundler").new({
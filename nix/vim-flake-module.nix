@@ -6,6 +6,64 @@ in {
     perSystem = mkPerSystemOption ({ pkgs, ... }:
       let
         inherit (lib) types mkEnableOption mkOption;
+        pluginConfigFile = types.addCheck (types.submodule {
+          options = {
+            language = mkOption {
+              type = with types; nullOr (enum [ "vim" ]);
+              description = "inferred from the extension of file when null";
+              default = null;
+              visible = false;
+            };
+            file = mkOption {
+              type = types.path;
+              description = "file read at build time";
+            };
+            args = mkOption {
+              type = types.attrs;
+              default = { };
+            };
+          };
+        }) (v: v ? file);
+        vimValues = with types;
+          attrsOf (oneOf [ bool int float str (listOf (oneOf [ bool int float str ])) ]);
+        keymap = types.submodule {
+          options = {
+            mode = mkOption {
+              type = with types; coercedTo str lib.toList (listOf str);
+              default = [ "n" ];
+            };
+            lhs = mkOption { type = types.str; };
+            rhs = mkOption {
+              type = types.str;
+              description = "keys";
+            };
+            desc = mkOption {
+              type = types.str;
+              default = "";
+            };
+            silent = mkEnableOption "silent";
+            buffer = mkEnableOption "buffer" // {
+              description = "buffer-local, defined once the plugin is loaded";
+            };
+          };
+        };
+        pluginConfigDetail = types.submodule {
+          options = {
+            language = mkOption {
+              type = types.enum [ "vim" ];
+              default = "vim";
+              visible = false;
+            };
+            code = mkOption {
+              type = types.lines;
+              default = "";
+            };
+            args = mkOption {
+              type = types.attrs;
+              default = { };
+            };
+          };
+        };
         vim = {
           target = mkOption {
             type = types.enum [ "vim" ];
@@ -26,38 +84,65 @@ in {
           };
           after = {
             ftplugin = mkOption {
-              type = with types; attrsOf lines;
+              type = with types;
+                let ftConfig = either lines (either pluginConfigFile pluginConfigDetail);
+                in attrsOf (coercedTo ftConfig lib.toList (listOf ftConfig));
+              description = "not yet support";
+              default = { };
+            };
+            plugin = mkOption {
+              type = with types; attrsOf (either lines (either pluginConfigFile pluginConfigDetail));
+              description = "not yet support";
+              default = { };
+            };
+            syntax = mkOption {
+              type = with types; attrsOf (either lines (either pluginConfigFile pluginConfigDetail));
+              description = "not yet support";
+              default = { };
+            };
+            indent = mkOption {
+              type = with types; attrsOf (either lines (either pluginConfigFile pluginConfigDetail));
+              description = "not yet support";
+              default = { };
+            };
+            lsp = mkOption {
+              type = with types; attrsOf (either lines (either pluginConfigFile pluginConfigDetail));
+              description = "not yet support";
+              default = { };
+              visible = false;
+            };
+            queries = mkOption {
+              type = with types; attrsOf (attrsOf lines);
               description = "not yet support";
               default = { };
+              visible = false;
             };
           };
         };
         bundlerPlugin = let
-          pluginConfigDetail = types.submodule {
-            options = {
-              language = mkOption {
-                type = types.enum [ "vim" ];
-                default = "vim";
-                visible = false;
-              };
-              code = mkOption {
-                type = types.lines;
-                default = "";
-              };
-              args = mkOption {
-                type = types.attrs;
-                default = { };
-              };
-            };
-          };
           eagerPluginConfig = types.submodule {
             options = {
               plugin = mkOption { type = types.package; };
               startupConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 description = "not yet support";
                 default = "";
               };
+              keymaps = mkOption {
+                type = types.listOf keymap;
+                description = "Keymaps to define for the plugin";
+                default = [ ];
+              };
+              options = mkOption {
+                type = vimValues;
+                description = "Options to set at startup";
+                default = { };
+              };
+              globals = mkOption {
+                type = vimValues;
+                description = "Global variables to set at startup";
+                default = { };
+              };
               extraPackages = mkOption {
                 type = with types; listOf package;
                 description = "not yet support";
@@ -70,22 +155,37 @@ in {
             options = {
               plugin = mkOption { type = types.package; };
               startupConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 description = "not yet support";
                 default = "";
               };
+              keymaps = mkOption {
+                type = types.listOf keymap;
+                description = "Keymaps to define for the plugin";
+                default = [ ];
+              };
+              options = mkOption {
+                type = vimValues;
+                description = "Options to set at startup";
+                default = { };
+              };
+              globals = mkOption {
+                type = vimValues;
+                description = "Global variables to set at startup";
+                default = { };
+              };
               extraPackages = mkOption {
                 type = with types; listOf package;
                 description = "not yet support";
                 default = [ ];
               };
               preConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 description = "not yet support";
                 default = "";
               };
               postConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 description = "not yet support";
                 default = "";
               };
@@ -120,6 +220,31 @@ in {
                 description = "not yet support";
                 default = [ ];
               };
+              onFunctions = mkOption {
+                type = with types; listOf str;
+                description = "not yet support";
+                default = [ ];
+              };
+              onSources = mkOption {
+                type = with types; listOf package;
+                description = "not yet support";
+                default = [ ];
+              };
+              onPlugins = mkOption {
+                type = with types; listOf package;
+                description = "not yet support";
+                default = [ ];
+              };
+              onColorschemes = mkOption {
+                type = with types; listOf str;
+                description = "not yet support";
+                default = [ ];
+              };
+              onRootMarkers = mkOption {
+                type = with types; listOf str;
+                description = "not yet support";
+                default = [ ];
+              };
               useTimer = mkOption {
                 type = types.bool;
                 description = "not yet support";
@@ -141,19 +266,24 @@ in {
                 default = [ ];
               };
               startupConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 default = "";
               };
+              keymaps = mkOption {
+                type = types.listOf keymap;
+                description = "Keymaps to define for the plugin";
+                default = [ ];
+              };
               extraPackages = mkOption {
                 type = with types; listOf package;
                 default = [ ];
               };
               preConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 default = "";
               };
               postConfig = mkOption {
-                type = with types; either lines pluginConfigDetail;
+                type = with types; either lines (either pluginConfigFile pluginConfigDetail);
                 default = "";
               };
               dependPlugins = mkOption {
@@ -181,7 +311,30 @@ in {
                 type = with types; listOf str;
                 default = [ ];
               };
+              onFunctions = mkOption {
+                type = with types; listOf str;
+                default = [ ];
+              };
+              onSources = mkOption {
+                type = with types; listOf package;
+                default = [ ];
+              };
+              onPlugins = mkOption {
+                type = with types; listOf package;
+                default = [ ];
+              };
+              onColorschemes = mkOption {
+                type = with types; listOf str;
+                description = "not yet support";
+                default = [ ];
+              };
+              onRootMarkers = mkOption {
+                type = with types; listOf str;
+                description = "not yet support";
+                default = [ ];
+              };
               useTimer = mkEnableOption "useTimer";
+              useDenops = mkEnableOption "useDenops";
             };
           };
         in {
@@ -197,6 +350,23 @@ in {
             type = types.listOf lazyGroupConfig;
             default = [ ];
           };
+          preferEager = mkEnableOption "preferEager";
+          autoTriggers = mkEnableOption "autoTriggers";
+          collisions = mkOption {
+            type = types.enum [ "off" "warn" "error" ];
+            default = "warn";
+          };
+          flattenEager = mkEnableOption "flattenEager" // { visible = false; };
+          options = mkOption {
+            type = vimValues;
+            description = "Options to set at startup";
+            default = { };
+          };
+          globals = mkOption {
+            type = vimValues;
+            description = "Global variables to set at startup";
+            default = { };
+          };
         };
       in {
         options.bundler-vim = mkOption {
@@ -223,7 +393,7 @@ in {
       let
         inherit (builtins) toJSON;
         inherit (lib) mapAttrs' nameValuePair flatten;
-        inherit (lib.lists) unique;
+        inherit (lib.lists) unique subtractLists;
         inherit (pkgs) writeText;
         inherit (pkgs.stdenv) mkDerivation;
 
@@ -249,7 +419,8 @@ in {
               ++ unique (flatten (map extractVimPlugins cfg.eagerPlugins));
             lazyVimPluginPackages =
               let plugins = with cfg; lazyPlugins ++ lazyGroups;
-              in unique (flatten (map extractVimPlugins plugins));
+              in subtractLists eagerVimPluginPackages
+              (unique (flatten (map extractVimPlugins plugins)));
 
             # TODO: support extra packages.
             extraPackages = [ ];